@@ -0,0 +1,111 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+
+use crate::cache::CacheStats;
+use crate::error::DidSovDriverError;
+use crate::network::Networks;
+use crate::resolve::refresh_did;
+use crate::state::AppState;
+
+/// Routes for managing the resolution cache, accessible with the configured bearer token.
+pub fn router(token: String) -> Router<AppState> {
+    Router::new()
+        .route("/admin/cache", delete(purge_all))
+        .route("/admin/cache/stats", get(stats))
+        .route("/admin/cache/:did", delete(purge))
+        .route("/admin/cache/:did/refresh", post(refresh))
+        .route_layer(middleware::from_fn_with_state(
+            Arc::new(token),
+            require_token,
+        ))
+}
+
+/// Compares in constant time so that the token cannot be guessed from response times.
+fn tokens_equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn require_token<B>(
+    State(token): State<Arc<String>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|provided| tokens_equal(provided.as_bytes(), token.as_bytes()));
+    if authorized {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response()
+    }
+}
+
+async fn purge(
+    Path(did): Path<String>,
+    Extension(networks): Extension<Arc<Networks>>,
+    State(state): State<AppState>,
+) -> Result<StatusCode, DidSovDriverError> {
+    info!("Purging cached resolutions of DID {}", did);
+    state
+        .cache
+        .purge(&networks.route(&did)?.canonical_did())
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn purge_all(State(state): State<AppState>) -> StatusCode {
+    info!("Purging all cached resolutions");
+    state.cache.purge_all().await;
+    StatusCode::NO_CONTENT
+}
+
+async fn refresh(
+    Path(did): Path<String>,
+    Extension(networks): Extension<Arc<Networks>>,
+    State(state): State<AppState>,
+) -> Result<StatusCode, DidSovDriverError> {
+    info!("Refreshing cached resolutions of DID {}", did);
+    // The cached outcome is replaced by the refreshed one, so that it is still served if the
+    // refresh fails. The other resolutions are only dropped once the DID could be read.
+    refresh_did(&did, &networks, &state).await?;
+    state
+        .cache
+        .purge_options(&networks.route(&did)?.canonical_did())
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn stats(State(state): State<AppState>) -> Json<CacheStats> {
+    Json(state.cache.stats())
+}
@@ -0,0 +1,111 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::{
+    extract::{rejection::JsonRejection, FromRequest, Query, State},
+    http::Request,
+    Extension, Json,
+};
+use futures::{stream, StreamExt};
+use serde_json::{json, Map, Value};
+
+use crate::config::ErrorFormat;
+use crate::error::DidSovDriverError;
+use crate::network::Networks;
+use crate::options::ResolutionParams;
+use crate::representation::ContentType;
+use crate::resolve::{finalize_response, resolve_did_with_cache};
+use crate::state::AppState;
+
+/// A JSON request body, rejected with `invalidRequest` rather than the plain text of `Json`.
+pub struct JsonBody<T>(pub T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for JsonBody<T>
+where
+    Json<T>: FromRequest<S, B, Rejection = JsonRejection>,
+    S: Send + Sync,
+    B: Send + 'static,
+{
+    type Rejection = DidSovDriverError;
+
+    async fn from_request(request: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(request, state)
+            .await
+            .map_err(|rejection| DidSovDriverError::InvalidRequest(rejection.body_text()))?;
+        Ok(Self(value))
+    }
+}
+
+/// Resolves each DID of a JSON array concurrently, with the resolution options of the query
+/// applied to all of them. Responds with an object mapping each DID to its status code and its DID
+/// Resolution Result, or to its problem details if errors are rendered as such.
+pub async fn resolve_dids(
+    Query(query): Query<HashMap<String, String>>,
+    Extension(networks): Extension<Arc<Networks>>,
+    State(state): State<AppState>,
+    JsonBody(dids): JsonBody<Vec<String>>,
+) -> Result<Json<Value>, DidSovDriverError> {
+    let params = ResolutionParams::from_query(query)?;
+    let dids = dids.into_iter().collect::<BTreeSet<_>>();
+    if dids.len() > state.batch.max_dids {
+        return Err(DidSovDriverError::InvalidRequest(format!(
+            "At most {} DIDs can be resolved in a batch, got {}",
+            state.batch.max_dids,
+            dids.len()
+        )));
+    }
+    debug!("Resolving a batch of {} DIDs", dids.len());
+
+    let results = stream::iter(dids)
+        .map(|did| {
+            let (params, networks, state) = (&params, &networks, &state);
+            async move {
+                let result =
+                    match resolve_did_with_cache(did.clone(), params, networks, state).await {
+                        Ok((response, _)) => {
+                            let representation =
+                                finalize_response(response, params, ContentType::DidResolution);
+                            json!({
+                                "status": 200,
+                                "didResolutionResult": representation.body(),
+                            })
+                        }
+                        Err(err) => {
+                            let error = err.to_cached();
+                            let (_, body) = error.render(state.error_format);
+                            match state.error_format {
+                                ErrorFormat::Resolution => json!({
+                                    "status": error.status_code.as_u16(),
+                                    "didResolutionResult": body,
+                                }),
+                                // Problem details carry the status already
+                                ErrorFormat::Problem => body,
+                            }
+                        }
+                    };
+                (did, result)
+            }
+        })
+        .buffer_unordered(state.batch.parallelism.max(1))
+        .collect::<Map<String, Value>>()
+        .await;
+    Ok(Json(Value::Object(results)))
+}
@@ -0,0 +1,213 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use sled::transaction::{ConflictableTransactionError, TransactionError};
+use sled::Transactional;
+
+use super::stored::StoredEntry;
+use super::{is_key_of_did, is_options_key_of_did, CacheBackend, CacheEntry};
+
+/// Key of an entry in the index of entries by insertion time.
+fn insertion_key(stored: &StoredEntry, key: &[u8]) -> Vec<u8> {
+    [&stored.inserted_at.to_be_bytes()[..], key].concat()
+}
+
+/// Cache persisted in an embedded key-value store, so that it survives restarts. When the
+/// capacity is exceeded, the entries inserted first are evicted.
+pub struct DiskCache {
+    entries: sled::Tree,
+    /// Keys of the entries ordered by insertion time.
+    insertions: sled::Tree,
+    capacity: usize,
+    /// Number of entries, as counting them in the store takes a full scan.
+    len: AtomicUsize,
+    /// Serializes writes, so that `len` and the evictions stay in step with the trees.
+    write_lock: Mutex<()>,
+    evictions: AtomicU64,
+}
+
+impl DiskCache {
+    pub fn open(path: &str, capacity: NonZeroUsize) -> Result<Self, anyhow::Error> {
+        let db = sled::open(path).with_context(|| format!("Failed to open cache at {}", path))?;
+        let entries = db.open_tree("entries")?;
+        let len = entries.len();
+        info!("Loaded {} cached resolutions from {}", len, path);
+        let cache = Self {
+            entries,
+            insertions: db.open_tree("insertions")?,
+            capacity: capacity.get(),
+            len: AtomicUsize::new(len),
+            write_lock: Mutex::new(()),
+            evictions: AtomicU64::new(0),
+        };
+        // The capacity may have been lowered since the cache was written
+        cache.evict_to_capacity();
+        Ok(cache)
+    }
+
+    fn stored(&self, key: &[u8]) -> Option<StoredEntry> {
+        match self.entries.get(key) {
+            Ok(value) => value.and_then(|value| serde_json::from_slice(&value).ok()),
+            Err(err) => {
+                error!("Failed to read cache entry: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Replaces the entry of `key` with `replacement`, a value and its key in `insertions`, or
+    /// removes it if there is none. Both trees are updated in a single transaction, so that an
+    /// entry is never persisted without its index entry or the other way around. Returns whether
+    /// there was an entry to replace. Must be called with the write lock held.
+    fn write_locked(
+        &self,
+        key: &[u8],
+        replacement: Option<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<bool, TransactionError<sled::Error>> {
+        let replaced = (&self.entries, &self.insertions).transaction(|(entries, insertions)| {
+            let previous = entries.remove(key)?;
+            if let Some(stored) = previous
+                .as_ref()
+                .and_then(|value| serde_json::from_slice::<StoredEntry>(value).ok())
+            {
+                insertions.remove(insertion_key(&stored, key))?;
+            }
+            if let Some((value, insertion)) = &replacement {
+                insertions.insert(insertion.as_slice(), key)?;
+                entries.insert(key, value.as_slice())?;
+            }
+            Ok::<_, ConflictableTransactionError<sled::Error>>(previous.is_some())
+        })?;
+        match (replaced, replacement.is_some()) {
+            (false, true) => {
+                self.len.fetch_add(1, Ordering::Relaxed);
+            }
+            (true, false) => {
+                self.len.fetch_sub(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+        Ok(replaced)
+    }
+
+    /// Must be called with the write lock held, or before the cache is shared.
+    fn evict_to_capacity(&self) {
+        while self.len.load(Ordering::Relaxed) > self.capacity {
+            let key = match self.insertions.first() {
+                Ok(Some((_, key))) => key,
+                Ok(None) => return,
+                Err(err) => {
+                    error!("Failed to evict cache entry: {}", err);
+                    return;
+                }
+            };
+            match self.write_locked(&key, None) {
+                Ok(true) => {
+                    self.evictions.fetch_add(1, Ordering::Relaxed);
+                }
+                // Left behind by a cache written before the index was kept transactionally
+                Ok(false) => {
+                    if let Err(err) = self.insertions.pop_min() {
+                        error!("Failed to evict cache entry: {}", err);
+                        return;
+                    }
+                }
+                Err(err) => {
+                    error!("Failed to evict cache entry: {}", err);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn remove_matching(&self, matches: impl Fn(&str) -> bool) {
+        let _guard = self.write_lock.lock().unwrap();
+        let keys = self
+            .entries
+            .iter()
+            .keys()
+            .filter_map(Result::ok)
+            .filter(|key| std::str::from_utf8(key).is_ok_and(&matches))
+            .collect::<Vec<_>>();
+        for key in keys {
+            if let Err(err) = self.write_locked(&key, None) {
+                error!("Failed to remove cache entry: {}", err);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl CacheBackend for DiskCache {
+    async fn get(&self, key: &str) -> Option<CacheEntry> {
+        self.stored(key.as_bytes())?.into_entry()
+    }
+
+    async fn put(&self, key: String, entry: CacheEntry, _retention: Duration) {
+        let stored = StoredEntry::from(&entry);
+        let value = match serde_json::to_vec(&stored) {
+            Ok(value) => value,
+            Err(err) => {
+                error!("Failed to serialize cache entry of {}: {}", key, err);
+                return;
+            }
+        };
+        let insertion = insertion_key(&stored, key.as_bytes());
+        let _guard = self.write_lock.lock().unwrap();
+        if let Err(err) = self.write_locked(key.as_bytes(), Some((value, insertion))) {
+            error!("Failed to write cache entry of {}: {}", key, err);
+        }
+        self.evict_to_capacity();
+    }
+
+    async fn expire(&self, key: &str) {
+        let _guard = self.write_lock.lock().unwrap();
+        if let Err(err) = self.write_locked(key.as_bytes(), None) {
+            error!("Failed to remove cache entry of {}: {}", key, err);
+        }
+    }
+
+    async fn remove_did(&self, did: &str) {
+        self.remove_matching(|key| is_key_of_did(key, did));
+    }
+
+    async fn remove_did_options(&self, did: &str) {
+        self.remove_matching(|key| is_options_key_of_did(key, did));
+    }
+
+    async fn clear(&self) {
+        let _guard = self.write_lock.lock().unwrap();
+        if let Err(err) = self.entries.clear().and(self.insertions.clear()) {
+            error!("Failed to clear cache: {}", err);
+        }
+        self.len.store(self.entries.len(), Ordering::Relaxed);
+    }
+
+    fn size(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+}
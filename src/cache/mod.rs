@@ -0,0 +1,315 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod disk;
+mod redis_cache;
+mod sharded;
+mod stored;
+
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::config::{CacheBackendKind, CacheConfig};
+use crate::error::CachedError;
+use crate::response::DIDJsonResponse;
+
+use self::disk::DiskCache;
+use self::redis_cache::RedisCache;
+use self::sharded::ShardedLruCache;
+
+pub type CachedResolution = Result<DIDJsonResponse, CachedError>;
+
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    /// Wall-clock time rather than an `Instant`, so that entries of persistent backends keep
+    /// their age across restarts.
+    pub inserted_at: SystemTime,
+    pub resolution: CachedResolution,
+}
+
+/// Outcome of a cache lookup.
+pub enum CacheLookup {
+    /// A resolution outcome within its TTL, with the time left until it expires.
+    Fresh(CachedResolution, Duration),
+    /// A resolution result past its TTL but within the max-stale bound, to be served while it is
+    /// refreshed in the background.
+    Stale(DIDJsonResponse),
+}
+
+/// Purge broadcast by another replica sharing the cache.
+#[derive(Debug)]
+pub enum Invalidation {
+    Did(String),
+    All,
+}
+
+/// Storage of cache entries. Expiration is decided by `ResolutionCache`, backends only need to
+/// bound their size.
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+    async fn get(&self, key: &str) -> Option<CacheEntry>;
+    /// Stores the entry, which may be dropped once it is older than `retention`.
+    async fn put(&self, key: String, entry: CacheEntry, retention: Duration);
+    /// Drops the entry of `key`, found expired. Backends shared by replicas only drop what they
+    /// hold locally, as the shared entry may have been refreshed by another replica, and is
+    /// dropped by the server once past its retention.
+    async fn expire(&self, key: &str);
+    /// Reads the entry of `key` again from where it is shared by replicas, bypassing any local
+    /// copy. Backends which are not shared have nothing newer to offer.
+    async fn reload(&self, _key: &str) -> Option<CacheEntry> {
+        None
+    }
+    /// Removes the entries of all resolutions of the DID, as selected by `is_key_of_did`.
+    async fn remove_did(&self, did: &str);
+    /// Removes the entries of the resolutions of the DID with options, as selected by
+    /// `is_options_key_of_did`, keeping the entry of the resolution without.
+    async fn remove_did_options(&self, did: &str);
+    async fn clear(&self);
+    fn size(&self) -> usize;
+    /// Number of entries evicted to stay within capacity since startup.
+    fn evictions(&self) -> u64;
+}
+
+/// Whether `key` is the cache key of a resolution of the DID, with any options.
+pub fn is_key_of_did(key: &str, did: &str) -> bool {
+    key == did || is_options_key_of_did(key, did)
+}
+
+/// Whether `key` is the cache key of a resolution of the DID with options.
+pub fn is_options_key_of_did(key: &str, did: &str) -> bool {
+    key.strip_prefix(did)
+        .is_some_and(|options| options.starts_with('?'))
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheStats {
+    pub size: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// Cache of resolution outcomes, keyed by DID and resolution options which affect the result.
+/// Errors which depend on the DID alone, such as `notFound`, are cached with a separate TTL.
+pub struct ResolutionCache {
+    backend: Box<dyn CacheBackend>,
+    config: CacheConfig,
+    /// Entries of the keys of prewarmed DIDs, kept apart from the backend so that they are never
+    /// evicted.
+    pinned: Mutex<HashMap<String, CacheEntry>>,
+    pinned_keys: HashSet<String>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ResolutionCache {
+    /// Creates the cache along with the backend, and applies the purges of the replicas sharing
+    /// it to the pinned entries for as long as the cache is alive.
+    pub async fn new(
+        config: CacheConfig,
+        pinned_keys: HashSet<String>,
+    ) -> Result<Arc<Self>, anyhow::Error> {
+        let capacity = match NonZeroUsize::new(config.capacity) {
+            Some(capacity) => capacity,
+            None if !config.enabled => NonZeroUsize::new(1).unwrap(),
+            None => anyhow::bail!("Cache capacity must be positive when the cache is enabled"),
+        };
+        let shards =
+            NonZeroUsize::new(config.shards).context("Number of cache shards must be positive")?;
+        let (invalidations, mut received) = mpsc::unbounded_channel();
+        let backend: Box<dyn CacheBackend> = match config.backend {
+            CacheBackendKind::Memory => Box::new(ShardedLruCache::new(capacity, shards)),
+            CacheBackendKind::Disk => Box::new(DiskCache::open(&config.path, capacity)?),
+            CacheBackendKind::Redis => Box::new(
+                RedisCache::connect(
+                    &config.redis_url,
+                    &config.redis_key_prefix,
+                    capacity,
+                    shards,
+                    invalidations,
+                )
+                .await?,
+            ),
+        };
+        let cache = Arc::new(Self {
+            backend,
+            config,
+            pinned: Mutex::new(HashMap::new()),
+            pinned_keys,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        });
+        // Ends right away for backends which are not shared, as they drop the sender
+        let weak = Arc::downgrade(&cache);
+        tokio::spawn(async move {
+            while let Some(invalidation) = received.recv().await {
+                match weak.upgrade() {
+                    Some(cache) => cache.forget_pinned(&invalidation),
+                    None => break,
+                }
+            }
+        });
+        Ok(cache)
+    }
+
+    /// TTL of outcomes of resolutions on the network with the given namespace, either successful
+    /// or `negative`, or `None` if they are not to be cached.
+    pub fn ttl(&self, namespace: &str, negative: bool) -> Option<Duration> {
+        let overrides = self.config.networks.get(namespace);
+        let enabled = overrides
+            .and_then(|overrides| overrides.enabled)
+            .unwrap_or(self.config.enabled);
+        let ttl_secs = if negative {
+            overrides
+                .and_then(|overrides| overrides.negative_ttl_secs)
+                .unwrap_or(self.config.negative_ttl_secs)
+        } else {
+            overrides
+                .and_then(|overrides| overrides.ttl_secs)
+                .unwrap_or(self.config.ttl_secs)
+        };
+        (enabled && ttl_secs > 0).then_some(Duration::from_secs(ttl_secs))
+    }
+
+    /// How long a resolution result may be served past its TTL, if stale-while-revalidate is on.
+    fn max_stale(&self) -> Option<Duration> {
+        self.config
+            .stale_while_revalidate
+            .then_some(Duration::from_secs(self.config.max_stale_secs))
+    }
+
+    pub async fn get(&self, namespace: &str, key: &str) -> Option<CacheLookup> {
+        let lookup = self.lookup(namespace, key).await;
+        let counter = match lookup {
+            Some(_) => &self.hits,
+            None => &self.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        lookup
+    }
+
+    async fn lookup(&self, namespace: &str, key: &str) -> Option<CacheLookup> {
+        let pinned = self.pinned.lock().unwrap().get(key).cloned();
+        let is_pinned = pinned.is_some();
+        let entry = match pinned {
+            Some(entry) => entry,
+            None => self.backend.get(key).await?,
+        };
+        let mut lookup = self.evaluate(namespace, &entry);
+        if !is_pinned && !matches!(lookup, Some(CacheLookup::Fresh(..))) {
+            // Another replica may have refreshed the entry in a shared backend
+            if let Some(reloaded) = self.backend.reload(key).await {
+                lookup = self.evaluate(namespace, &reloaded).or(lookup);
+            }
+        }
+        match lookup {
+            Some(CacheLookup::Fresh(..)) => debug!("Cache hit for DID {}", key),
+            Some(CacheLookup::Stale(_)) => debug!("Cache hit for DID {}, stale", key),
+            None => {
+                debug!("Cache expired for DID {}", key);
+                if is_pinned {
+                    self.pinned.lock().unwrap().remove(key);
+                } else {
+                    self.backend.expire(key).await;
+                }
+            }
+        }
+        lookup
+    }
+
+    /// Whether `entry` may still be served, and how.
+    fn evaluate(&self, namespace: &str, entry: &CacheEntry) -> Option<CacheLookup> {
+        // An entry from the future, e.g. after the clock was set back, is treated as new
+        let age = entry.inserted_at.elapsed().unwrap_or_default();
+        let ttl = self.ttl(namespace, entry.resolution.is_err())?;
+        match &entry.resolution {
+            resolution if age < ttl => Some(CacheLookup::Fresh(resolution.clone(), ttl - age)),
+            Ok(response)
+                if self
+                    .max_stale()
+                    .is_some_and(|max_stale| age < ttl + max_stale) =>
+            {
+                Some(CacheLookup::Stale(response.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    pub async fn put(&self, namespace: &str, key: String, resolution: CachedResolution) {
+        if let Some(ttl) = self.ttl(namespace, resolution.is_err()) {
+            let retention = ttl + self.max_stale().unwrap_or_default();
+            let entry = CacheEntry {
+                inserted_at: SystemTime::now(),
+                resolution,
+            };
+            if self.pinned_keys.contains(&key) {
+                self.pinned.lock().unwrap().insert(key, entry);
+            } else {
+                self.backend.put(key, entry, retention).await;
+            }
+        }
+    }
+
+    /// Removes the cached outcomes of all resolutions of the DID, with any options.
+    pub async fn purge(&self, did: &str) {
+        self.forget_pinned(&Invalidation::Did(did.to_string()));
+        self.backend.remove_did(did).await;
+    }
+
+    /// Removes the cached outcomes of the resolutions of the DID with options, such as of its
+    /// historical versions, keeping the outcome of the resolution of its latest version.
+    pub async fn purge_options(&self, did: &str) {
+        self.pinned
+            .lock()
+            .unwrap()
+            .retain(|key, _| !is_options_key_of_did(key, did));
+        self.backend.remove_did_options(did).await;
+    }
+
+    pub async fn purge_all(&self) {
+        self.forget_pinned(&Invalidation::All);
+        self.backend.clear().await;
+    }
+
+    /// Drops the pinned entries covered by a purge, either made here or by another replica. The
+    /// backend drops its own entries.
+    fn forget_pinned(&self, invalidation: &Invalidation) {
+        let mut pinned = self.pinned.lock().unwrap();
+        match invalidation {
+            Invalidation::Did(did) => pinned.retain(|key, _| !is_key_of_did(key, did)),
+            Invalidation::All => pinned.clear(),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            size: self.backend.size() + self.pinned.lock().unwrap().len(),
+            capacity: self.config.capacity,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.backend.evictions(),
+        }
+    }
+}
@@ -0,0 +1,276 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use futures::StreamExt;
+use redis::{aio::ConnectionManager, AsyncCommands, RedisResult};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::sharded::ShardedLruCache;
+use super::stored::StoredEntry;
+use super::{CacheBackend, CacheEntry, Invalidation};
+
+const CLEAR_MESSAGE: &str = "clear";
+const PURGE_MESSAGE_PREFIX: &str = "purge ";
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Cache shared by the replicas of the driver on a Redis-protocol server, fronted by a local LRU
+/// cache. Purges are broadcast to the other replicas, which drop their local copies and pass the
+/// purges on to be applied to their pinned entries.
+pub struct RedisCache {
+    connection: ConnectionManager,
+    local: Arc<ShardedLruCache>,
+    key_prefix: String,
+    channel: String,
+}
+
+impl RedisCache {
+    pub async fn connect(
+        url: &str,
+        key_prefix: &str,
+        capacity: NonZeroUsize,
+        shards: NonZeroUsize,
+        invalidations: UnboundedSender<Invalidation>,
+    ) -> Result<Self, anyhow::Error> {
+        let client =
+            redis::Client::open(url).with_context(|| format!("Invalid redis URL {}", url))?;
+        let connection = ConnectionManager::new(client.clone())
+            .await
+            .with_context(|| format!("Failed to connect to redis at {}", url))?;
+        info!("Caching resolutions in redis at {}", url);
+
+        let local = Arc::new(ShardedLruCache::new(capacity, shards));
+        let channel = format!("{}:invalidations", key_prefix);
+        tokio::spawn(subscribe_to_invalidations(
+            client,
+            channel.clone(),
+            local.clone(),
+            invalidations,
+        ));
+        Ok(Self {
+            connection,
+            local,
+            key_prefix: key_prefix.to_string(),
+            channel,
+        })
+    }
+
+    fn redis_key(&self, key: &str) -> String {
+        format!("{}:{}", self.key_prefix, key)
+    }
+
+    async fn fetch(&self, key: &str) -> RedisResult<Option<CacheEntry>> {
+        let mut connection = self.connection.clone();
+        let value: Option<Vec<u8>> = connection.get(self.redis_key(key)).await?;
+        Ok(value
+            .and_then(|value| serde_json::from_slice::<StoredEntry>(&value).ok())
+            .and_then(StoredEntry::into_entry))
+    }
+
+    async fn store(&self, key: &str, entry: &CacheEntry, retention: Duration) -> RedisResult<()> {
+        let value = serde_json::to_vec(&StoredEntry::from(entry)).map_err(|err| {
+            redis::RedisError::from((
+                redis::ErrorKind::TypeError,
+                "Failed to serialize cache entry",
+                err.to_string(),
+            ))
+        })?;
+        let mut connection = self.connection.clone();
+        connection
+            .pset_ex(
+                self.redis_key(key),
+                value,
+                retention.as_millis().max(1) as usize,
+            )
+            .await
+    }
+
+    /// Deletes the keys matching the glob-style `pattern`.
+    async fn delete_matching(&self, pattern: &str) -> RedisResult<()> {
+        let mut connection = self.connection.clone();
+        let keys = {
+            let mut iter = connection.scan_match::<_, String>(pattern).await?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        if !keys.is_empty() {
+            connection.del::<_, ()>(keys).await?;
+        }
+        Ok(())
+    }
+
+    async fn publish(&self, message: String) {
+        let mut connection = self.connection.clone();
+        if let Err(err) = connection.publish::<_, _, ()>(&self.channel, message).await {
+            error!("Failed to broadcast cache invalidation: {}", err);
+        }
+    }
+}
+
+fn escape_glob(pattern: &str) -> String {
+    pattern
+        .chars()
+        .flat_map(|c| match c {
+            '*' | '?' | '[' | ']' | '\\' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
+
+/// Applies the purges broadcast by the replicas to the local cache and passes them on to
+/// `invalidations` until the process exits.
+async fn subscribe_to_invalidations(
+    client: redis::Client,
+    channel: String,
+    local: Arc<ShardedLruCache>,
+    invalidations: UnboundedSender<Invalidation>,
+) {
+    loop {
+        if let Err(err) = apply_invalidations(&client, &channel, &local, &invalidations).await {
+            error!("Subscription to cache invalidations failed: {}", err);
+        }
+        // Purges broadcast while not subscribed are missed, so start over
+        local.clear().await;
+        let _ = invalidations.send(Invalidation::All);
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
+
+async fn apply_invalidations(
+    client: &redis::Client,
+    channel: &str,
+    local: &ShardedLruCache,
+    invalidations: &UnboundedSender<Invalidation>,
+) -> RedisResult<()> {
+    let mut pubsub = client.get_async_connection().await?.into_pubsub();
+    pubsub.subscribe(channel).await?;
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        let payload: String = message.get_payload()?;
+        // Nobody listens once the cache is dropped, which only happens at exit
+        if payload == CLEAR_MESSAGE {
+            local.clear().await;
+            let _ = invalidations.send(Invalidation::All);
+        } else if let Some(did) = payload.strip_prefix(PURGE_MESSAGE_PREFIX) {
+            debug!(
+                "Dropping cached resolutions of DID {} purged by a replica",
+                did
+            );
+            local.remove_did(did).await;
+            let _ = invalidations.send(Invalidation::Did(did.to_string()));
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl CacheBackend for RedisCache {
+    async fn get(&self, key: &str) -> Option<CacheEntry> {
+        match self.local.get(key).await {
+            Some(entry) => Some(entry),
+            None => self.reload(key).await,
+        }
+    }
+
+    async fn reload(&self, key: &str) -> Option<CacheEntry> {
+        self.local.expire(key).await;
+        match self.fetch(key).await {
+            Ok(entry) => {
+                let entry = entry?;
+                // The local cache does not expire entries on its own
+                self.local
+                    .put(key.to_string(), entry.clone(), Duration::ZERO)
+                    .await;
+                Some(entry)
+            }
+            Err(err) => {
+                error!("Failed to read cache entry of {} from redis: {}", key, err);
+                None
+            }
+        }
+    }
+
+    async fn put(&self, key: String, entry: CacheEntry, retention: Duration) {
+        if let Err(err) = self.store(&key, &entry, retention).await {
+            error!("Failed to write cache entry of {} to redis: {}", key, err);
+        }
+        self.local.put(key, entry, retention).await;
+    }
+
+    /// Drops the local copy only, the entry in redis expires on its own.
+    async fn expire(&self, key: &str) {
+        self.local.expire(key).await;
+    }
+
+    async fn remove_did(&self, did: &str) {
+        let key = escape_glob(&self.redis_key(did));
+        let result = async {
+            self.delete_matching(&key).await?;
+            self.delete_matching(&format!("{}\\?*", key)).await
+        };
+        if let Err(err) = result.await {
+            error!(
+                "Failed to purge cache entries of {} from redis: {}",
+                did, err
+            );
+        }
+        self.local.remove_did(did).await;
+        self.publish(format!("{}{}", PURGE_MESSAGE_PREFIX, did))
+            .await;
+    }
+
+    async fn remove_did_options(&self, did: &str) {
+        let pattern = format!("{}\\?*", escape_glob(&self.redis_key(did)));
+        if let Err(err) = self.delete_matching(&pattern).await {
+            error!(
+                "Failed to purge cache entries of {} from redis: {}",
+                did, err
+            );
+        }
+        self.local.remove_did_options(did).await;
+        // Replicas drop their local copies of all resolutions of the DID, and read the one kept
+        // here from redis again
+        self.publish(format!("{}{}", PURGE_MESSAGE_PREFIX, did))
+            .await;
+    }
+
+    async fn clear(&self) {
+        let pattern = format!("{}:*", escape_glob(&self.key_prefix));
+        if let Err(err) = self.delete_matching(&pattern).await {
+            error!("Failed to clear cache entries from redis: {}", err);
+        }
+        self.local.clear().await;
+        self.publish(CLEAR_MESSAGE.to_string()).await;
+    }
+
+    /// Only the local copies are counted, as counting the entries on the server takes a full
+    /// scan of its keys.
+    fn size(&self) -> usize {
+        self.local.size()
+    }
+
+    fn evictions(&self) -> u64 {
+        self.local.evictions()
+    }
+}
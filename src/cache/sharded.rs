@@ -0,0 +1,114 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use lru::LruCache;
+
+use super::{is_key_of_did, is_options_key_of_did, CacheBackend, CacheEntry};
+
+/// In-memory LRU cache split into independently locked shards, so that requests for different
+/// DIDs rarely contend for the same lock. Recency is tracked per shard.
+pub struct ShardedLruCache {
+    shards: Vec<Mutex<LruCache<String, CacheEntry>>>,
+    evictions: AtomicU64,
+}
+
+impl ShardedLruCache {
+    pub fn new(capacity: NonZeroUsize, shards: NonZeroUsize) -> Self {
+        let shard_count = shards.get().min(capacity.get());
+        let shard_capacity = NonZeroUsize::new(capacity.get().div_ceil(shard_count))
+            .expect("Shard capacity is positive for positive capacity");
+        Self {
+            shards: (0..shard_count)
+                .map(|_| Mutex::new(LruCache::new(shard_capacity)))
+                .collect(),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    fn remove_matching(&self, matches: impl Fn(&str) -> bool) {
+        for shard in &self.shards {
+            let mut shard = shard.lock().unwrap();
+            let keys = shard
+                .iter()
+                .map(|(key, _)| key)
+                .filter(|key| matches(key))
+                .cloned()
+                .collect::<Vec<_>>();
+            for key in keys {
+                shard.pop(&key);
+            }
+        }
+    }
+
+    fn shard(&self, key: &str) -> &Mutex<LruCache<String, CacheEntry>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+}
+
+#[async_trait]
+impl CacheBackend for ShardedLruCache {
+    async fn get(&self, key: &str) -> Option<CacheEntry> {
+        self.shard(key).lock().unwrap().get(key).cloned()
+    }
+
+    async fn put(&self, key: String, entry: CacheEntry, _retention: Duration) {
+        let shard = self.shard(&key);
+        // Pushing returns either the replaced entry of the same key or the evicted one
+        let replaced = shard.lock().unwrap().push(key.clone(), entry);
+        if replaced.is_some_and(|(replaced_key, _)| replaced_key != key) {
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    async fn expire(&self, key: &str) {
+        self.shard(key).lock().unwrap().pop(key);
+    }
+
+    async fn remove_did(&self, did: &str) {
+        self.remove_matching(|key| is_key_of_did(key, did));
+    }
+
+    async fn remove_did_options(&self, did: &str) {
+        self.remove_matching(|key| is_options_key_of_did(key, did));
+    }
+
+    async fn clear(&self) {
+        for shard in &self.shards {
+            shard.lock().unwrap().clear();
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().len())
+            .sum()
+    }
+
+    fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,86 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::CacheEntry;
+use crate::error::{CachedError, ErrorKind};
+use crate::response::DIDJsonResponse;
+
+/// Cache entry as stored outside of the process, with the insertion time in milliseconds since the
+/// Unix epoch so that TTLs are respected across restarts and replicas.
+#[derive(Serialize, Deserialize)]
+pub struct StoredEntry {
+    pub inserted_at: u64,
+    resolution: StoredResolution,
+}
+
+#[derive(Serialize, Deserialize)]
+enum StoredResolution {
+    Ok(Value),
+    Err {
+        status_code: u16,
+        body: Value,
+        kind: String,
+    },
+}
+
+impl From<&CacheEntry> for StoredEntry {
+    fn from(entry: &CacheEntry) -> Self {
+        let inserted_at = entry
+            .inserted_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let resolution = match &entry.resolution {
+            Ok(response) => StoredResolution::Ok(response.0.clone()),
+            Err(err) => StoredResolution::Err {
+                status_code: err.status_code.as_u16(),
+                body: err.body.clone(),
+                kind: err.kind.0.to_string(),
+            },
+        };
+        Self {
+            inserted_at,
+            resolution,
+        }
+    }
+}
+
+impl StoredEntry {
+    pub fn into_entry(self) -> Option<CacheEntry> {
+        let resolution = match self.resolution {
+            StoredResolution::Ok(value) => Ok(DIDJsonResponse(value)),
+            StoredResolution::Err {
+                status_code,
+                body,
+                kind,
+            } => Err(CachedError {
+                status_code: StatusCode::from_u16(status_code).ok()?,
+                body,
+                kind: ErrorKind::from_label(&kind)?,
+            }),
+        };
+        Some(CacheEntry {
+            inserted_at: UNIX_EPOCH + Duration::from_millis(self.inserted_at),
+            resolution,
+        })
+    }
+}
@@ -0,0 +1,124 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::watch;
+
+use crate::cache::CachedResolution;
+use crate::error::DidSovDriverError;
+use crate::response::DIDJsonResponse;
+
+type InFlight = watch::Receiver<Option<CachedResolution>>;
+
+/// Lets concurrent resolutions of the same key share a single ledger read.
+#[derive(Default)]
+pub struct Coalescer {
+    in_flight: Mutex<HashMap<String, InFlight>>,
+}
+
+enum Role {
+    Leader(watch::Sender<Option<CachedResolution>>),
+    Follower(InFlight),
+}
+
+/// Unregisters an in-flight resolution when it completes or is cancelled.
+struct InFlightGuard {
+    coalescer: Arc<Coalescer>,
+    key: String,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = self.coalescer.in_flight.lock() {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+impl Coalescer {
+    /// Runs `resolution` in a task of its own unless a resolution of the same key is already in
+    /// flight, in which case its outcome is awaited instead. Either way, the resolution completes
+    /// even if the request waiting for it is cancelled. Errors are shared in their rendered form.
+    /// Should the in-flight resolution fail to complete, the waiting requests elect a new leader
+    /// among themselves, so the key is still read only once. Details of the resolution which are
+    /// not to be shared, such as how it went, are returned only to the request which started it.
+    pub async fn run<F, T>(
+        self: &Arc<Self>,
+        key: &str,
+        resolution: F,
+    ) -> (Result<DIDJsonResponse, DidSovDriverError>, Option<T>)
+    where
+        F: Future<Output = (Result<DIDJsonResponse, DidSovDriverError>, T)> + Send + 'static,
+        T: Send + 'static,
+    {
+        loop {
+            let role = {
+                let mut in_flight = self.in_flight.lock().unwrap();
+                match in_flight.get(key) {
+                    Some(receiver) => Role::Follower(receiver.clone()),
+                    None => {
+                        let (sender, receiver) = watch::channel(None);
+                        in_flight.insert(key.to_string(), receiver);
+                        Role::Leader(sender)
+                    }
+                }
+            };
+
+            match role {
+                Role::Leader(sender) => {
+                    let guard = InFlightGuard {
+                        coalescer: self.clone(),
+                        key: key.to_string(),
+                    };
+                    let task = tokio::spawn(async move {
+                        let _guard = guard;
+                        let (result, details) = resolution.await;
+                        let shared = match &result {
+                            Ok(response) => Ok(response.clone()),
+                            Err(err) => Err(err.to_cached()),
+                        };
+                        // There may be no followers, in which case nobody is listening
+                        let _ = sender.send(Some(shared));
+                        (result, details)
+                    });
+                    return match task.await {
+                        Ok((result, details)) => (result, Some(details)),
+                        Err(err) => (Err(DidSovDriverError::Other(Box::new(err))), None),
+                    };
+                }
+                Role::Follower(mut receiver) => {
+                    loop {
+                        let shared = receiver.borrow().clone();
+                        if let Some(shared) = shared {
+                            debug!("Resolution of {} shared with an in-flight request", key);
+                            return (shared.map_err(DidSovDriverError::Cached), None);
+                        }
+                        if receiver.changed().await.is_err() {
+                            break;
+                        }
+                    }
+                    debug!(
+                        "In-flight resolution of {} did not complete, electing a new leader",
+                        key
+                    );
+                }
+            }
+        }
+    }
+}
@@ -14,9 +14,12 @@
  * limitations under the License.
  */
 
+use std::collections::HashMap;
+use std::time::Duration;
+
 use ::config as configrs;
 use anyhow::Context;
-use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
 
 #[derive(Debug)]
 pub struct LogLevel(pub tracing::Level);
@@ -29,14 +32,222 @@ pub struct WalletConfig {
 }
 
 #[derive(Debug, Deserialize)]
-pub struct PoolConfig {
+pub struct NetworkConfig {
     pub name: String,
-    pub network: String,
+    pub pool_name: String,
+    pub genesis: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PoolConfig {
+    pub default_network: String,
+    pub networks: HashMap<String, NetworkConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CacheOverrideConfig {
+    pub enabled: Option<bool>,
+    pub ttl_secs: Option<u64>,
+    pub negative_ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackendKind {
+    Memory,
+    Disk,
+    Redis,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PrewarmConfig {
+    pub dids: Vec<String>,
+    /// File listing DIDs one per line, in addition to `dids`.
+    pub file: Option<String>,
+    pub refresh_interval_secs: u64,
+}
+
+impl Default for PrewarmConfig {
+    fn default() -> Self {
+        Self {
+            dids: Vec::new(),
+            file: None,
+            refresh_interval_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub backend: CacheBackendKind,
+    /// Directory of the disk backend.
+    pub path: String,
+    pub redis_url: String,
+    /// Prefix of the keys of the redis backend, to tell apart deployments sharing a server.
+    pub redis_key_prefix: String,
+    pub capacity: usize,
+    pub shards: usize,
+    pub ttl_secs: u64,
+    pub negative_ttl_secs: u64,
+    pub stale_while_revalidate: bool,
+    pub max_stale_secs: u64,
+    /// Capacity of the cache of ledger responses kept by the resolver, which is not purged along
+    /// with the cache of the driver. Zero disables it.
+    pub resolver_capacity: usize,
+    pub networks: HashMap<String, CacheOverrideConfig>,
+    pub prewarm: PrewarmConfig,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            backend: CacheBackendKind::Memory,
+            path: "cache".to_string(),
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            redis_key_prefix: "driver-did-sov".to_string(),
+            capacity: 100,
+            shards: 16,
+            ttl_secs: 60,
+            negative_ttl_secs: 10,
+            stale_while_revalidate: false,
+            max_stale_secs: 300,
+            resolver_capacity: 0,
+            networks: HashMap::new(),
+            prewarm: PrewarmConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BatchConfig {
+    /// Maximum number of DIDs accepted in a single batch request.
+    pub max_dids: usize,
+    /// Maximum number of DIDs of a batch resolved concurrently.
+    pub parallelism: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_dids: 1000,
+            parallelism: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Attempts of a ledger read, including the first one.
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Kinds of the errors on which a ledger read is retried, e.g. `ledger_timeout`.
+    pub retryable: Vec<String>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 2000,
+            retryable: vec![
+                "ledger_timeout".to_string(),
+                "ledger_unavailable".to_string(),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ResolutionConfig {
+    /// Deadline of a resolution unless the client asks for another one.
+    pub timeout_ms: u64,
+    /// Upper bound of the deadline a client may ask for.
+    pub max_timeout_ms: u64,
+    pub retry: RetryConfig,
+}
+
+impl Default for ResolutionConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 8000,
+            max_timeout_ms: 30000,
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+impl ResolutionConfig {
+    /// The deadline requested by the client, if any, capped by the maximum.
+    pub fn deadline(&self, requested: Option<Duration>) -> Duration {
+        requested
+            .unwrap_or(Duration::from_millis(self.timeout_ms))
+            .min(Duration::from_millis(self.max_timeout_ms))
+    }
+
+    /// Deadline of a read from the ledger shared by concurrent resolutions, which is as long as
+    /// any of them may wait.
+    pub fn shared_deadline(&self) -> Duration {
+        Duration::from_millis(self.max_timeout_ms)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// OTLP gRPC endpoint spans are exported to, if any.
+    pub otlp_endpoint: Option<String>,
+    pub service_name: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: env!("CARGO_PKG_NAME").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AdminConfig {
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Format of the body of error responses.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorFormat {
+    /// A DID Resolution Result with `didResolutionMetadata.error` set and no DID document.
+    #[default]
+    Resolution,
+    /// An RFC 7807 `application/problem+json` object.
+    Problem,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ApplicationConfig {
     pub log_level: LogLevel,
+    #[serde(default)]
+    pub log_format: LogFormat,
+    #[serde(default)]
+    pub error_format: ErrorFormat,
     pub port: u16,
 }
 
@@ -45,6 +256,16 @@ pub struct Config {
     pub pool: PoolConfig,
     pub wallet: WalletConfig,
     pub application: ApplicationConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub resolution: ResolutionConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
 }
 
 impl<'de> Deserialize<'de> for LogLevel {
@@ -84,7 +305,13 @@ fn load_config<T: DeserializeOwned>() -> Result<T, anyhow::Error> {
         None => {
             info!("Configuration will be loaded from environment variables");
             configrs::Config::builder()
-                .add_source(configrs::Environment::default().separator("::"))
+                .add_source(
+                    configrs::Environment::default()
+                        .separator("::")
+                        .list_separator(",")
+                        .with_list_parse_key("cache.prewarm.dids")
+                        .with_list_parse_key("resolution.retry.retryable"),
+                )
                 .build()?
         }
     };
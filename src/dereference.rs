@@ -0,0 +1,309 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use did_resolver_sov::error::DIDSovError;
+use serde_json::{json, Map, Value};
+use url::{form_urlencoded, Url};
+
+use crate::error::DidSovDriverError;
+use crate::options::ResolutionParams;
+use crate::representation::ContentType;
+use crate::response::DIDJsonResponse;
+
+const SERVICE_PARAMETER: &str = "service";
+const RELATIVE_REF_PARAMETER: &str = "relativeRef";
+const URI_LIST_MEDIA_TYPE: &str = "text/uri-list";
+
+// Properties of a DID document which may contain resources addressable by a fragment
+const FRAGMENT_PROPERTIES: [&str; 7] = [
+    "verificationMethod",
+    "authentication",
+    "assertionMethod",
+    "keyAgreement",
+    "capabilityInvocation",
+    "capabilityDelegation",
+    "service",
+];
+
+/// A DID URL split into the DID and its path, query and fragment components.
+#[derive(Debug, Clone)]
+pub struct DidUrl {
+    pub did: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub fragment: Option<String>,
+}
+
+impl DidUrl {
+    pub fn parse(did_url: &str) -> Self {
+        let (rest, fragment) = match did_url.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment.to_string())),
+            None => (did_url, None),
+        };
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (
+                rest,
+                form_urlencoded::parse(query.as_bytes())
+                    .into_owned()
+                    .collect(),
+            ),
+            None => (rest, HashMap::new()),
+        };
+        let (did, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+        Self {
+            did: did.to_string(),
+            path: path.to_string(),
+            query,
+            fragment,
+        }
+    }
+
+    /// Moves the DID parameters which select a resource from the query of the HTTP request,
+    /// where clients may put them rather than percent-encode them into the DID URL, to the DID
+    /// URL. Resolution options of the same names do not exist.
+    pub fn take_dereferencing_parameters(
+        &mut self,
+        query: &mut HashMap<String, String>,
+    ) -> Result<(), DidSovDriverError> {
+        for name in [SERVICE_PARAMETER, RELATIVE_REF_PARAMETER] {
+            if let Some(value) = query.remove(name) {
+                if self.query.contains_key(name) {
+                    return Err(DidSovDriverError::InvalidDidUrl(format!(
+                        "{} is given both in the DID URL and in the query",
+                        name
+                    )));
+                }
+                self.query.insert(name.to_string(), value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the DID URL selects a resource other than the DID document itself. DID URLs
+    /// carrying only DID parameters such as `versionTime` are resolved rather than dereferenced.
+    pub fn selects_resource(&self) -> bool {
+        !self.path.is_empty()
+            || self.fragment.is_some()
+            || self.query.contains_key(SERVICE_PARAMETER)
+    }
+
+    /// Applies the DID parameters which affect resolution of the DID to `params`.
+    pub fn apply_did_parameters(
+        &self,
+        params: &mut ResolutionParams,
+    ) -> Result<(), DidSovDriverError> {
+        for (name, value) in &self.query {
+            if name == SERVICE_PARAMETER || name == RELATIVE_REF_PARAMETER {
+                continue;
+            }
+            if !params.apply_option(name, value.clone())? {
+                params.unknown.push(name.clone());
+            }
+        }
+        params.unknown.sort();
+        Ok(())
+    }
+}
+
+/// The primary resource selected by a DID URL.
+#[derive(Debug)]
+enum ContentStream {
+    Resource(Value),
+    Redirect(Url),
+}
+
+/// A DID URL Dereferencing Result rendered in the representation negotiated with the client.
+#[derive(Debug)]
+pub struct DereferencingResponse {
+    content_type: ContentType,
+    content_stream: ContentStream,
+    content_metadata: Value,
+    dereferencing_metadata: Map<String, Value>,
+}
+
+pub fn dereference(
+    did_url: &DidUrl,
+    resolution: DIDJsonResponse,
+    params: &ResolutionParams,
+    content_type: ContentType,
+) -> Result<DereferencingResponse, DidSovDriverError> {
+    if !did_url.path.is_empty() {
+        return Err(DIDSovError::NotFound(format!(
+            "DID URL paths are not supported by did:sov: {}",
+            did_url.path
+        ))
+        .into());
+    }
+
+    let document = resolution.did_document();
+    let (content_stream, content_metadata) = match did_url.query.get(SERVICE_PARAMETER) {
+        Some(service) => {
+            let redirect = service_redirect(did_url, document, service)?;
+            (ContentStream::Redirect(redirect), json!({}))
+        }
+        None => match &did_url.fragment {
+            Some(fragment) => {
+                let resource = select_fragment(document, &did_url.did, fragment)?;
+                (ContentStream::Resource(resource), json!({}))
+            }
+            None => (
+                ContentStream::Resource(document.clone()),
+                resolution.did_document_metadata().clone(),
+            ),
+        },
+    };
+
+    let mut dereferencing_metadata = Map::new();
+    if !params.unknown.is_empty() {
+        dereferencing_metadata.insert("unknownOptions".to_string(), json!(params.unknown));
+    }
+    Ok(DereferencingResponse {
+        content_type,
+        content_stream,
+        content_metadata,
+        dereferencing_metadata,
+    })
+}
+
+fn matches_fragment(id: &str, did: &str, fragment: &str) -> bool {
+    match id.split_once('#') {
+        Some((base, id_fragment)) => (base.is_empty() || base == did) && id_fragment == fragment,
+        None => false,
+    }
+}
+
+fn select_fragment(
+    document: &Value,
+    did: &str,
+    fragment: &str,
+) -> Result<Value, DidSovDriverError> {
+    FRAGMENT_PROPERTIES
+        .iter()
+        .filter_map(|property| document[property].as_array())
+        .flatten()
+        .find(|resource| {
+            resource["id"]
+                .as_str()
+                .is_some_and(|id| matches_fragment(id, did, fragment))
+        })
+        .cloned()
+        .ok_or_else(|| {
+            DIDSovError::NotFound(format!(
+                "No resource with fragment #{} in {}",
+                fragment, did
+            ))
+            .into()
+        })
+}
+
+/// Builds the URL selected by the `service` and `relativeRef` DID parameters as described in
+/// the DID Core specification. A fragment of the DID URL is carried over to the result.
+fn service_redirect(
+    did_url: &DidUrl,
+    document: &Value,
+    service: &str,
+) -> Result<Url, DidSovDriverError> {
+    let service_endpoint = document["service"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|entry| {
+            entry["id"]
+                .as_str()
+                .is_some_and(|id| matches_fragment(id, &did_url.did, service))
+        })
+        .ok_or_else(|| {
+            DidSovDriverError::from(DIDSovError::NotFound(format!(
+                "No service {} in {}",
+                service, did_url.did
+            )))
+        })?["serviceEndpoint"]
+        .as_str()
+        .ok_or_else(|| {
+            DidSovDriverError::InvalidDidUrl(format!(
+                "Service {} does not have a URL service endpoint",
+                service
+            ))
+        })?;
+
+    let invalid_url = |err: url::ParseError| {
+        DidSovDriverError::InvalidDidUrl(format!(
+            "Cannot construct a URL for service {}: {}",
+            service, err
+        ))
+    };
+    let mut url = Url::parse(service_endpoint).map_err(invalid_url)?;
+    if let Some(relative_ref) = did_url.query.get(RELATIVE_REF_PARAMETER) {
+        url = url.join(relative_ref).map_err(invalid_url)?;
+    }
+    if let Some(fragment) = &did_url.fragment {
+        url.set_fragment(Some(fragment));
+    }
+    Ok(url)
+}
+
+impl IntoResponse for DereferencingResponse {
+    fn into_response(self) -> Response {
+        if self.content_type == ContentType::DidUrlDereferencing {
+            let (content_stream, stream_content_type) = match self.content_stream {
+                ContentStream::Resource(resource) => (resource, ContentType::DidJson.media_type()),
+                ContentStream::Redirect(url) => (json!(url.as_str()), URI_LIST_MEDIA_TYPE),
+            };
+            let mut dereferencing_metadata = self.dereferencing_metadata;
+            dereferencing_metadata.insert("contentType".to_string(), json!(stream_content_type));
+            let mut res = Json(json!({
+                "dereferencingMetadata": dereferencing_metadata,
+                "contentStream": content_stream,
+                "contentMetadata": self.content_metadata,
+            }))
+            .into_response();
+            res.headers_mut().insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(self.content_type.media_type()),
+            );
+            return res;
+        }
+
+        match self.content_stream {
+            ContentStream::Resource(resource) => {
+                let media_type = match self.content_type {
+                    ContentType::DidLdJson => ContentType::DidLdJson.media_type(),
+                    _ => ContentType::DidJson.media_type(),
+                };
+                let mut res = Json(resource).into_response();
+                res.headers_mut()
+                    .insert(header::CONTENT_TYPE, HeaderValue::from_static(media_type));
+                res
+            }
+            ContentStream::Redirect(url) => match HeaderValue::from_str(url.as_str()) {
+                Ok(location) => {
+                    (StatusCode::SEE_OTHER, [(header::LOCATION, location)]).into_response()
+                }
+                Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            },
+        }
+    }
+}
@@ -14,9 +14,13 @@
  * limitations under the License.
  */
 
+use aries_vcx::aries_vcx_core::errors::error::{AriesVcxCoreError, AriesVcxCoreErrorKind};
 use axum::{
+    body::{boxed, Full},
+    extract::State,
+    http::{header, HeaderValue, Request},
+    middleware::Next,
     response::{IntoResponse, Response},
-    Json,
 };
 use did_resolver_sov::{
     did_resolver::{
@@ -25,21 +29,309 @@ use did_resolver_sov::{
     error::DIDSovError,
 };
 use hyper::StatusCode;
-use serde_json::json;
+use serde_json::{json, Value};
 use thiserror::Error;
 
+use crate::config::ErrorFormat;
+use crate::representation::DID_RESOLUTION_MEDIA_TYPE;
+use crate::response::DRIVER_METADATA_KEY;
+use crate::state::AppState;
+
+const PROBLEM_MEDIA_TYPE: &str = "application/problem+json";
+const PROBLEM_TYPE_PREFIX: &str = "urn:driver-did-sov:error:";
+/// Seconds clients are asked to wait before retrying after the ledger failed to answer.
+const RETRY_AFTER_SECS: u64 = 5;
+
+/// Codes of the vdrtools errors reported by the ledger client as `VdrToolsError`.
+const POOL_LEDGER_INVALID_HANDLE: u32 = 301;
+const POOL_LEDGER_TERMINATED: u32 = 302;
+const LEDGER_NO_CONSENSUS: u32 = 303;
+const POOL_LEDGER_TIMEOUT: u32 = 307;
+
+/// An error response kept in the resolution cache, served as it was originally rendered.
+#[derive(Debug, Clone)]
+pub struct CachedError {
+    pub status_code: StatusCode,
+    pub body: Value,
+    /// Kind of the error the response was rendered from, so that it is still reported as such.
+    pub kind: ErrorKind,
+}
+
+impl std::fmt::Display for CachedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.body["details"], self.status_code)
+    }
+}
+
+impl CachedError {
+    /// The DID Resolution error code, e.g. `notFound`.
+    pub fn error(&self) -> &str {
+        self.body["error"].as_str().unwrap_or("internalError")
+    }
+
+    /// Seconds to wait before retrying, for errors which are likely to be transient.
+    pub fn retry_after(&self) -> Option<u64> {
+        match self.status_code {
+            StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT => Some(RETRY_AFTER_SECS),
+            _ => None,
+        }
+    }
+
+    /// Renders the error in the given format, along with the media type of the rendering.
+    pub fn render(&self, format: ErrorFormat) -> (&'static str, Value) {
+        let error = self.error();
+        let retries = self.body.get("retries");
+        match format {
+            ErrorFormat::Resolution => {
+                let mut metadata = json!({
+                    "contentType": DID_RESOLUTION_MEDIA_TYPE,
+                    "error": error,
+                    "errorMessage": self.body["details"],
+                });
+                if let Some(retries) = retries {
+                    metadata[DRIVER_METADATA_KEY] = json!({ "retries": retries });
+                }
+                (
+                    DID_RESOLUTION_MEDIA_TYPE,
+                    json!({
+                        "didDocument": null,
+                        "didDocumentMetadata": {},
+                        "didResolutionMetadata": metadata,
+                    }),
+                )
+            }
+            ErrorFormat::Problem => {
+                let mut problem = json!({
+                    "type": format!("{}{}", PROBLEM_TYPE_PREFIX, error),
+                    "title": problem_title(error),
+                    "status": self.status_code.as_u16(),
+                    "detail": self.body["details"],
+                    "error": error,
+                });
+                if let Some(retries) = retries {
+                    problem["retries"] = retries.clone();
+                }
+                (PROBLEM_MEDIA_TYPE, problem)
+            }
+        }
+    }
+}
+
+fn problem_title(error: &str) -> &'static str {
+    match error {
+        "invalidDid" => "Invalid DID",
+        "notFound" => "DID not found",
+        "methodNotSupported" => "DID method not supported",
+        "representationNotSupported" => "Representation not supported",
+        "invalidOptions" => "Invalid resolution options",
+        "invalidDidUrl" => "Invalid DID URL",
+        "invalidRequest" => "Invalid request",
+        "ledgerTimeout" => "Ledger timeout",
+        "ledgerUnavailable" => "Ledger unavailable",
+        "malformedLedgerData" => "Malformed ledger data",
+        _ => "Internal error",
+    }
+}
+
+/// The variant of the error a response was rendered from, kept in the response extensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorKind(pub &'static str);
+
+/// Every kind returned by `DidSovDriverError::kind`.
+const ERROR_KINDS: [&str; 10] = [
+    "parse_error",
+    "resolve_error",
+    "representation_not_supported",
+    "invalid_options",
+    "invalid_did_url",
+    "invalid_request",
+    "ledger_timeout",
+    "ledger_unavailable",
+    "malformed_ledger_data",
+    "other",
+];
+
+impl ErrorKind {
+    /// The kind with the given label, e.g. as stored along with a cached error.
+    pub fn from_label(label: &str) -> Option<Self> {
+        ERROR_KINDS
+            .iter()
+            .find(|kind| **kind == label)
+            .map(|kind| ErrorKind(kind))
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum DidSovDriverError {
     #[error("Invalid DID: {0}")]
     ParseError(#[from] ParseError),
     #[error("Resolver error: {0}")]
     ResolveError(#[from] DIDSovError),
+    #[error("Representation not supported: {0}")]
+    RepresentationNotSupported(String),
+    #[error("Invalid resolution options: {0}")]
+    InvalidOptions(String),
+    #[error("Invalid DID URL: {0}")]
+    InvalidDidUrl(String),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Ledger timeout: {0}")]
+    LedgerTimeout(String),
+    #[error("Ledger unavailable: {0}")]
+    LedgerUnavailable(String),
+    #[error("Malformed ledger data: {0}")]
+    MalformedLedgerData(String),
     #[error("Generic error: {0}")]
     Other(#[from] Box<dyn std::error::Error + Send + Sync>),
+    #[error("{error} (after {retries} retries)")]
+    Retried {
+        error: Box<DidSovDriverError>,
+        retries: u32,
+    },
+    #[error("Cached error: {0}")]
+    Cached(CachedError),
 }
 
-impl IntoResponse for DidSovDriverError {
-    fn into_response(self) -> Response {
+/// Whether the error is one of those with a status code of its own.
+fn is_mapped_did_sov_error(err: &DIDSovError) -> bool {
+    matches!(
+        err,
+        DIDSovError::InvalidDID(_)
+            | DIDSovError::NotFound(_)
+            | DIDSovError::MethodNotSupported(_)
+            | DIDSovError::RepresentationNotSupported(_)
+    )
+}
+
+/// The error followed by its sources.
+fn error_chain<'a>(
+    err: &'a (dyn std::error::Error + 'static),
+) -> impl Iterator<Item = &'a (dyn std::error::Error + 'static)> {
+    std::iter::successors(Some(err), |err| err.source())
+}
+
+/// Describes the error followed by its sources.
+fn describe_error_chain(err: &(dyn std::error::Error + 'static)) -> String {
+    error_chain(err)
+        .map(|err| err.to_string())
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+/// Ways in which the ledger may fail to answer a read.
+#[derive(Debug, PartialEq, Eq)]
+enum LedgerFailure {
+    Timeout,
+    Unavailable,
+    MalformedData,
+}
+
+impl LedgerFailure {
+    /// Tells the failure from the kind of an error of the ledger client.
+    fn from_ledger_error(err: &AriesVcxCoreError) -> Option<Self> {
+        match err.kind() {
+            AriesVcxCoreErrorKind::VdrToolsError(POOL_LEDGER_TIMEOUT) => Some(Self::Timeout),
+            AriesVcxCoreErrorKind::PoolLedgerConnect
+            | AriesVcxCoreErrorKind::NoPoolOpen
+            | AriesVcxCoreErrorKind::PostMessageFailed
+            | AriesVcxCoreErrorKind::VdrToolsError(
+                POOL_LEDGER_INVALID_HANDLE | POOL_LEDGER_TERMINATED | LEDGER_NO_CONSENSUS,
+            ) => Some(Self::Unavailable),
+            AriesVcxCoreErrorKind::InvalidLedgerResponse
+            | AriesVcxCoreErrorKind::InvalidJson
+            | AriesVcxCoreErrorKind::SerializationError => Some(Self::MalformedData),
+            _ => None,
+        }
+    }
+
+    /// Tells the failure from the first error in the chain which is structured enough to tell.
+    /// Errors known only by their description are not told apart, as a description mentioning
+    /// e.g. a pool or JSON may as well come from a failure of the driver itself.
+    fn from_error(err: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        error_chain(err).find_map(|err| {
+            if let Some(err) = err.downcast_ref::<AriesVcxCoreError>() {
+                Self::from_ledger_error(err)
+            } else if err.is::<serde_json::Error>() {
+                Some(Self::MalformedData)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl DidSovDriverError {
+    /// Tells failures of the ledger apart from internal errors by the kind of the error of the
+    /// ledger client. Errors of unknown kinds are left as they are.
+    pub fn classify(self) -> Self {
+        let err: &(dyn std::error::Error + 'static) = match &self {
+            DidSovDriverError::ResolveError(err) if !is_mapped_did_sov_error(err) => err,
+            DidSovDriverError::Other(err)
+                if !err.is::<ParseError>()
+                    && !err
+                        .downcast_ref::<DIDSovError>()
+                        .is_some_and(is_mapped_did_sov_error) =>
+            {
+                err.as_ref()
+            }
+            _ => return self,
+        };
+        let details = describe_error_chain(err);
+        match LedgerFailure::from_error(err) {
+            Some(LedgerFailure::Timeout) => DidSovDriverError::LedgerTimeout(details),
+            Some(LedgerFailure::Unavailable) => DidSovDriverError::LedgerUnavailable(details),
+            Some(LedgerFailure::MalformedData) => DidSovDriverError::MalformedLedgerData(details),
+            None => self,
+        }
+    }
+
+    /// Whether the error is determined by the DID alone, so that it can be cached like a
+    /// resolution result.
+    pub fn is_cacheable(&self) -> bool {
+        let is_cacheable_did_sov_error = |err: &DIDSovError| {
+            matches!(err, DIDSovError::InvalidDID(_) | DIDSovError::NotFound(_))
+        };
+        match self {
+            DidSovDriverError::ParseError(_) => true,
+            DidSovDriverError::ResolveError(err) => is_cacheable_did_sov_error(err),
+            DidSovDriverError::Other(err) => {
+                err.is::<ParseError>()
+                    || err
+                        .downcast_ref::<DIDSovError>()
+                        .is_some_and(is_cacheable_did_sov_error)
+            }
+            DidSovDriverError::Retried { error, .. } => error.is_cacheable(),
+            _ => false,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind(match self {
+            DidSovDriverError::ParseError(_) => "parse_error",
+            DidSovDriverError::ResolveError(_) => "resolve_error",
+            DidSovDriverError::RepresentationNotSupported(_) => "representation_not_supported",
+            DidSovDriverError::InvalidOptions(_) => "invalid_options",
+            DidSovDriverError::InvalidDidUrl(_) => "invalid_did_url",
+            DidSovDriverError::InvalidRequest(_) => "invalid_request",
+            DidSovDriverError::LedgerTimeout(_) => "ledger_timeout",
+            DidSovDriverError::LedgerUnavailable(_) => "ledger_unavailable",
+            DidSovDriverError::MalformedLedgerData(_) => "malformed_ledger_data",
+            DidSovDriverError::Other(_) => "other",
+            DidSovDriverError::Retried { error, .. } => error.kind().0,
+            DidSovDriverError::Cached(err) => err.kind.0,
+        })
+    }
+
+    pub fn to_cached(&self) -> CachedError {
+        let (status_code, body) = self.status_code_and_body();
+        CachedError {
+            status_code,
+            body,
+            kind: self.kind(),
+        }
+    }
+
+    fn status_code_and_body(&self) -> (StatusCode, Value) {
         let handle_did_sov_error = |err: &DIDSovError| {
             let (status_code, description) = match err {
                 DIDSovError::InvalidDID(_) => (
@@ -81,7 +373,7 @@ impl IntoResponse for DidSovDriverError {
                 }),
             )
         };
-        let handle_generic_error = |err: Box<dyn std::error::Error + Send + Sync>| {
+        let handle_generic_error = |err: &(dyn std::error::Error + Send + Sync)| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 json!({
@@ -90,19 +382,220 @@ impl IntoResponse for DidSovDriverError {
                 }),
             )
         };
-        let (status_code, body) = match self {
-            DidSovDriverError::ParseError(err) => handle_parse_error(&err),
-            DidSovDriverError::ResolveError(err) => handle_did_sov_error(&err),
+        match self {
+            DidSovDriverError::ParseError(err) => handle_parse_error(err),
+            DidSovDriverError::ResolveError(err) => handle_did_sov_error(err),
+            DidSovDriverError::RepresentationNotSupported(details) => (
+                StatusCode::NOT_ACCEPTABLE,
+                json!({
+                    "error": DIDResolutionError::RepresentationNotSupported.to_string(),
+                    "details": details,
+                }),
+            ),
+            DidSovDriverError::InvalidOptions(details) => (
+                StatusCode::BAD_REQUEST,
+                json!({
+                    "error": "invalidOptions",
+                    "details": details,
+                }),
+            ),
+            DidSovDriverError::InvalidDidUrl(details) => (
+                StatusCode::BAD_REQUEST,
+                json!({
+                    "error": "invalidDidUrl",
+                    "details": details,
+                }),
+            ),
+            DidSovDriverError::InvalidRequest(details) => (
+                StatusCode::BAD_REQUEST,
+                json!({
+                    "error": "invalidRequest",
+                    "details": details,
+                }),
+            ),
+            DidSovDriverError::LedgerTimeout(details) => (
+                StatusCode::GATEWAY_TIMEOUT,
+                json!({
+                    "error": "ledgerTimeout",
+                    "details": details,
+                }),
+            ),
+            DidSovDriverError::LedgerUnavailable(details) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                json!({
+                    "error": "ledgerUnavailable",
+                    "details": details,
+                }),
+            ),
+            DidSovDriverError::MalformedLedgerData(details) => (
+                StatusCode::BAD_GATEWAY,
+                json!({
+                    "error": "malformedLedgerData",
+                    "details": details,
+                }),
+            ),
             DidSovDriverError::Other(err) => {
                 if let Some(err) = err.downcast_ref::<DIDSovError>() {
                     handle_did_sov_error(err)
                 } else if let Some(err) = err.downcast_ref::<ParseError>() {
                     handle_parse_error(err)
                 } else {
-                    handle_generic_error(err)
+                    handle_generic_error(err.as_ref())
                 }
             }
-        };
-        IntoResponse::into_response((status_code, Json(body)))
+            DidSovDriverError::Retried { error, retries } => {
+                let (status_code, mut body) = error.status_code_and_body();
+                body["retries"] = json!(retries);
+                (status_code, body)
+            }
+            DidSovDriverError::Cached(err) => (err.status_code, err.body.clone()),
+        }
+    }
+}
+
+impl IntoResponse for DidSovDriverError {
+    fn into_response(self) -> Response {
+        let error = self.to_cached();
+        let (media_type, body) = error.render(ErrorFormat::default());
+        let mut res = IntoResponse::into_response((
+            error.status_code,
+            [(header::CONTENT_TYPE, media_type)],
+            body.to_string(),
+        ));
+        if let Some(retry_after) = error.retry_after() {
+            res.headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        res.extensions_mut().insert(self.kind());
+        res.extensions_mut().insert(error);
+        res
+    }
+}
+
+/// Renders the body of error responses in the configured format.
+pub async fn render_errors<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let response = next.run(request).await;
+    let error = match response.extensions().get::<CachedError>() {
+        Some(error) => error.clone(),
+        None => return response,
+    };
+    let (media_type, body) = error.render(state.error_format);
+    let (mut parts, _) = response.into_parts();
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(media_type));
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, boxed(Full::from(body.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ledger_error(kind: AriesVcxCoreErrorKind) -> DidSovDriverError {
+        DidSovDriverError::Other(Box::new(AriesVcxCoreError::from_msg(
+            kind,
+            "Ledger read failed",
+        )))
+    }
+
+    fn described_error(description: &str) -> DidSovDriverError {
+        DidSovDriverError::Other(description.into())
+    }
+
+    #[test]
+    fn test_classify_ledger_timeout() {
+        let err = ledger_error(AriesVcxCoreErrorKind::VdrToolsError(POOL_LEDGER_TIMEOUT));
+        assert!(matches!(
+            err.classify(),
+            DidSovDriverError::LedgerTimeout(_)
+        ));
+    }
+
+    #[test]
+    fn test_classify_ledger_unavailable() {
+        for kind in [
+            AriesVcxCoreErrorKind::PoolLedgerConnect,
+            AriesVcxCoreErrorKind::NoPoolOpen,
+            AriesVcxCoreErrorKind::PostMessageFailed,
+            AriesVcxCoreErrorKind::VdrToolsError(POOL_LEDGER_INVALID_HANDLE),
+            AriesVcxCoreErrorKind::VdrToolsError(POOL_LEDGER_TERMINATED),
+            AriesVcxCoreErrorKind::VdrToolsError(LEDGER_NO_CONSENSUS),
+        ] {
+            let description = format!("{:?}", kind);
+            let classified = ledger_error(kind).classify();
+            assert!(
+                matches!(classified, DidSovDriverError::LedgerUnavailable(_)),
+                "{} classified as {:?}",
+                description,
+                classified
+            );
+        }
+    }
+
+    #[test]
+    fn test_classify_malformed_ledger_data() {
+        for kind in [
+            AriesVcxCoreErrorKind::InvalidLedgerResponse,
+            AriesVcxCoreErrorKind::InvalidJson,
+            AriesVcxCoreErrorKind::SerializationError,
+        ] {
+            let description = format!("{:?}", kind);
+            let classified = ledger_error(kind).classify();
+            assert!(
+                matches!(classified, DidSovDriverError::MalformedLedgerData(_)),
+                "{} classified as {:?}",
+                description,
+                classified
+            );
+        }
+        let err = serde_json::from_str::<Value>("{").unwrap_err();
+        assert!(matches!(
+            DidSovDriverError::Other(Box::new(err)).classify(),
+            DidSovDriverError::MalformedLedgerData(_)
+        ));
+    }
+
+    #[test]
+    fn test_classify_ignores_description() {
+        for description in [
+            "Request timed out",
+            "No consensus reached",
+            "Invalid ledger response",
+            "Failed to connect to the pool",
+            "Failed to serialize JSON",
+        ] {
+            let classified = described_error(description).classify();
+            assert!(
+                matches!(classified, DidSovDriverError::Other(_)),
+                "{} classified as {:?}",
+                description,
+                classified
+            );
+        }
+        assert!(matches!(
+            ledger_error(AriesVcxCoreErrorKind::VdrToolsError(
+                POOL_LEDGER_TIMEOUT + 1000
+            ))
+            .classify(),
+            DidSovDriverError::Other(_)
+        ));
+    }
+
+    #[test]
+    fn test_classify_keeps_other_errors() {
+        assert!(matches!(
+            described_error("Something else went wrong").classify(),
+            DidSovDriverError::Other(_)
+        ));
+        assert!(matches!(
+            DidSovDriverError::from(DIDSovError::NotFound("Pool has no such DID".to_string()))
+                .classify(),
+            DidSovDriverError::ResolveError(DIDSovError::NotFound(_))
+        ));
     }
 }
@@ -0,0 +1,77 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{http::StatusCode, Extension, Json};
+use futures::future::join_all;
+use serde_json::{json, Map, Value};
+
+use crate::network::{Network, Networks};
+
+// The first transaction of the domain ledger, which is part of the genesis of every network
+const PROBE_TXN_SEQ_NO: i32 = 1;
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reads a known transaction from the ledger of the network, which requires its pool to reach
+/// the ledger nodes.
+async fn probe_network(network: &Network) -> Result<(), String> {
+    let reply = tokio::time::timeout(
+        PROBE_TIMEOUT,
+        network.ledger.get_ledger_txn(PROBE_TXN_SEQ_NO, None),
+    )
+    .await
+    .map_err(|_| format!("Ledger did not respond within {:?}", PROBE_TIMEOUT))?
+    .map_err(|err| format!("Ledger read failed: {}", err))?;
+    let reply: Value =
+        serde_json::from_str(&reply).map_err(|err| format!("Malformed ledger reply: {}", err))?;
+    if reply["op"] != "REPLY" || reply["result"]["data"].is_null() {
+        return Err(format!("Unexpected ledger reply: {}", reply));
+    }
+    Ok(())
+}
+
+pub async fn live() -> Json<Value> {
+    Json(json!({ "status": "up" }))
+}
+
+/// Reports whether the ledger of every configured network can be read, per network.
+pub async fn ready(Extension(networks): Extension<Arc<Networks>>) -> (StatusCode, Json<Value>) {
+    let probes = networks.iter().map(|(namespace, network)| async move {
+        let status = match probe_network(network).await {
+            Ok(()) => json!({ "name": network.name, "status": "up" }),
+            Err(err) => {
+                warn!("Network {} is not ready: {}", network.name, err);
+                json!({ "name": network.name, "status": "down", "error": err })
+            }
+        };
+        (namespace.clone(), status)
+    });
+    let statuses = join_all(probes).await.into_iter().collect::<Map<_, _>>();
+
+    let ready = statuses.values().all(|status| status["status"] == "up");
+    let status_code = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = json!({
+        "status": if ready { "up" } else { "down" },
+        "networks": statuses,
+    });
+    (status_code, Json(body))
+}
@@ -0,0 +1,397 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aries_vcx::aries_vcx_core::ledger::base_ledger::BaseLedger;
+use chrono::{SecondsFormat, TimeZone, Utc};
+use did_resolver_sov::did_resolver::did_parser::ParsedDID;
+use did_resolver_sov::error::DIDSovError;
+use serde_json::{json, Value};
+
+use crate::error::DidSovDriverError;
+use crate::options::VersionSelector;
+use crate::response::DIDJsonResponse;
+
+const TXN_TYPE_NYM: &str = "1";
+const TXN_TYPE_ATTRIB: &str = "100";
+const TXN_TYPE_GET_ATTR: &str = "104";
+const TXN_TYPE_GET_NYM: &str = "105";
+
+// Reads of the ledger spent looking for the version following a historical one, each of which
+// goes one version back from the latest
+const MAX_NEXT_VERSION_READS: usize = 4;
+
+// Reads of the ledger spent looking for the first NYM of a DID, each of which goes one NYM back
+const MAX_CREATED_READS: usize = 4;
+
+// Read requests do not need to be signed, but the ledger still expects a submitter identifier
+const READ_REQUEST_SUBMITTER: &str = "LibindyDid111111111111";
+
+/// A NYM or ATTRIB transaction as seen in the ledger state.
+#[derive(Debug, Clone)]
+struct LedgerEntry {
+    seq_no: u64,
+    txn_time: i64,
+    data: Value,
+}
+
+/// The NYM and endpoint ATTRIB of a DID in effect at some point on the ledger.
+#[derive(Debug, Clone)]
+struct DocumentVersion {
+    nym: LedgerEntry,
+    endpoint: Option<LedgerEntry>,
+}
+
+impl DocumentVersion {
+    fn version_id(&self) -> u64 {
+        self.endpoint.as_ref().map_or(self.nym.seq_no, |endpoint| {
+            endpoint.seq_no.max(self.nym.seq_no)
+        })
+    }
+
+    fn updated(&self) -> i64 {
+        self.endpoint
+            .as_ref()
+            .map_or(self.nym.txn_time, |endpoint| {
+                endpoint.txn_time.max(self.nym.txn_time)
+            })
+    }
+}
+
+/// Resolves did:sov documents as they were at a given point on the ledger by issuing
+/// `GET_NYM` and `GET_ATTR` requests pinned to a timestamp.
+pub struct HistoricalResolver {
+    ledger: Arc<dyn BaseLedger>,
+}
+
+impl HistoricalResolver {
+    pub fn new(ledger: Arc<dyn BaseLedger>) -> Self {
+        Self { ledger }
+    }
+
+    pub async fn resolve(
+        &self,
+        parsed_did: &ParsedDID,
+        selector: VersionSelector,
+    ) -> Result<DIDJsonResponse, DidSovDriverError> {
+        if parsed_did.method() != "sov" {
+            return Err(DIDSovError::MethodNotSupported(parsed_did.method().to_string()).into());
+        }
+        let did_id = parsed_did.id();
+
+        let (timestamp, version_id) = match selector {
+            VersionSelector::Id(seq_no) => (
+                self.txn_time_of_version(did_id, seq_no).await?,
+                Some(seq_no),
+            ),
+            VersionSelector::Time(time) => (time.timestamp(), None),
+        };
+        let version = self
+            .version_at(did_id, Some(timestamp))
+            .await?
+            .ok_or_else(|| {
+                DIDSovError::NotFound(format!(
+                    "DID {} did not exist at {}",
+                    parsed_did.did(),
+                    format_timestamp(timestamp)
+                ))
+            })?;
+        let (created, next_version) = futures::try_join!(
+            self.created(did_id, &version.nym),
+            self.next_version(did_id, &version),
+        )?;
+
+        build_did_json_response(
+            parsed_did.did(),
+            did_id,
+            &version,
+            // Transactions written in the same second as the requested one share its version
+            version_id.unwrap_or_else(|| version.version_id()),
+            created,
+            next_version.as_ref(),
+        )
+    }
+
+    /// Looks up the transaction with the given sequence number and checks that it belongs to
+    /// the resolved DID.
+    async fn txn_time_of_version(
+        &self,
+        did_id: &str,
+        seq_no: u64,
+    ) -> Result<i64, DidSovDriverError> {
+        let not_found =
+            || DIDSovError::NotFound(format!("Version {} not found for DID {}", seq_no, did_id));
+
+        let seq_no_i32 = i32::try_from(seq_no).map_err(|_| not_found())?;
+        let reply = self
+            .ledger
+            .get_ledger_txn(seq_no_i32, None)
+            .await
+            .map_err(|err| DidSovDriverError::Other(Box::new(err)))?;
+        let result = parse_reply(&reply)?;
+
+        let txn = &result["data"]["txn"];
+        let is_did_txn = matches!(txn["type"].as_str(), Some(TXN_TYPE_NYM | TXN_TYPE_ATTRIB))
+            && txn["data"]["dest"].as_str() == Some(did_id);
+        if !is_did_txn {
+            return Err(not_found().into());
+        }
+        result["data"]["txnMetadata"]["txnTime"]
+            .as_i64()
+            .ok_or_else(|| malformed_ledger_data("Transaction is missing txnTime"))
+    }
+
+    async fn version_at(
+        &self,
+        did_id: &str,
+        timestamp: Option<i64>,
+    ) -> Result<Option<DocumentVersion>, DidSovDriverError> {
+        let (nym, endpoint) = futures::try_join!(
+            self.read_entry(get_nym_operation(did_id, timestamp)),
+            self.read_entry(get_attr_operation(did_id, timestamp)),
+        )?;
+        Ok(nym.map(|nym| DocumentVersion { nym, endpoint }))
+    }
+
+    /// Time of the first NYM of the DID, found by walking back from `nym` one NYM at a time. This
+    /// takes a ledger read per verkey rotation, which are rare. Gives up after
+    /// `MAX_CREATED_READS`, as the verkey of the DID may have been rotated many times.
+    async fn created(
+        &self,
+        did_id: &str,
+        nym: &LedgerEntry,
+    ) -> Result<Option<i64>, DidSovDriverError> {
+        let mut created = nym.txn_time;
+        for _ in 0..MAX_CREATED_READS {
+            match self
+                .read_entry(get_nym_operation(did_id, Some(created - 1)))
+                .await?
+            {
+                Some(earlier) => created = earlier.txn_time,
+                None => return Ok(Some(created)),
+            }
+        }
+        debug!(
+            "First NYM of DID {} not found within {} reads",
+            did_id, MAX_CREATED_READS
+        );
+        Ok(None)
+    }
+
+    /// Finds the version superseding `version` by walking back from the latest version of the DID,
+    /// reading the version in effect just before each one. Gives up after
+    /// `MAX_NEXT_VERSION_READS`, as the DID may have been updated many times since.
+    async fn next_version(
+        &self,
+        did_id: &str,
+        version: &DocumentVersion,
+    ) -> Result<Option<DocumentVersion>, DidSovDriverError> {
+        let mut next = match self.version_at(did_id, None).await? {
+            Some(latest) if latest.version_id() != version.version_id() => latest,
+            _ => return Ok(None),
+        };
+        for _ in 0..MAX_NEXT_VERSION_READS {
+            match self.version_at(did_id, Some(next.updated() - 1)).await? {
+                Some(previous) if previous.version_id() != version.version_id() => {
+                    next = previous;
+                }
+                _ => return Ok(Some(next)),
+            }
+        }
+        debug!(
+            "Version after {} of DID {} not found within {} reads",
+            version.version_id(),
+            did_id,
+            MAX_NEXT_VERSION_READS
+        );
+        Ok(None)
+    }
+
+    async fn read_entry(&self, operation: Value) -> Result<Option<LedgerEntry>, DidSovDriverError> {
+        let request = build_read_request(operation);
+        let reply = self
+            .ledger
+            .submit_request(&request)
+            .await
+            .map_err(|err| DidSovDriverError::Other(Box::new(err)))?;
+        let result = parse_reply(&reply)?;
+
+        let data = match result["data"].as_str() {
+            Some(data) => serde_json::from_str::<Value>(data)
+                .map_err(|err| malformed_ledger_data(&err.to_string()))?,
+            None => return Ok(None),
+        };
+        let seq_no = result["seqNo"]
+            .as_u64()
+            .or_else(|| data["seqNo"].as_u64())
+            .ok_or_else(|| malformed_ledger_data("Reply is missing seqNo"))?;
+        let txn_time = result["txnTime"]
+            .as_i64()
+            .or_else(|| data["txnTime"].as_i64())
+            .ok_or_else(|| malformed_ledger_data("Reply is missing txnTime"))?;
+        Ok(Some(LedgerEntry {
+            seq_no,
+            txn_time,
+            data,
+        }))
+    }
+}
+
+fn get_nym_operation(did_id: &str, timestamp: Option<i64>) -> Value {
+    let mut operation = json!({ "type": TXN_TYPE_GET_NYM, "dest": did_id });
+    if let Some(timestamp) = timestamp {
+        operation["timestamp"] = json!(timestamp);
+    }
+    operation
+}
+
+fn get_attr_operation(did_id: &str, timestamp: Option<i64>) -> Value {
+    let mut operation = json!({ "type": TXN_TYPE_GET_ATTR, "dest": did_id, "raw": "endpoint" });
+    if let Some(timestamp) = timestamp {
+        operation["timestamp"] = json!(timestamp);
+    }
+    operation
+}
+
+fn build_read_request(operation: Value) -> String {
+    // Requests read concurrently need distinct identifiers for their replies to be told apart
+    static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+    let req_id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() as u64)
+        .wrapping_add(REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed));
+    json!({
+        "reqId": req_id,
+        "identifier": READ_REQUEST_SUBMITTER,
+        "operation": operation,
+        "protocolVersion": 2,
+    })
+    .to_string()
+}
+
+fn parse_reply(reply: &str) -> Result<Value, DidSovDriverError> {
+    let mut reply: Value =
+        serde_json::from_str(reply).map_err(|err| malformed_ledger_data(&err.to_string()))?;
+    match reply["op"].as_str() {
+        Some("REPLY") => Ok(reply
+            .get_mut("result")
+            .map(Value::take)
+            .unwrap_or(Value::Null)),
+        _ => Err(malformed_ledger_data(&format!(
+            "Ledger rejected the request: {}",
+            reply["reason"].as_str().unwrap_or("unknown reason")
+        ))),
+    }
+}
+
+fn malformed_ledger_data(details: &str) -> DidSovDriverError {
+    DidSovDriverError::MalformedLedgerData(details.to_string())
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    Utc.timestamp_opt(timestamp, 0).single().map_or_else(
+        || timestamp.to_string(),
+        |time| time.to_rfc3339_opts(SecondsFormat::Secs, true),
+    )
+}
+
+/// Expands an abbreviated verkey (`~` followed by the second half of the key) into the full key.
+fn full_verkey(did_id: &str, verkey: &str) -> Result<String, DidSovDriverError> {
+    match verkey.strip_prefix('~') {
+        Some(abbreviated) => {
+            let mut key = bs58::decode(did_id)
+                .into_vec()
+                .map_err(|err| malformed_ledger_data(&err.to_string()))?;
+            key.extend(
+                bs58::decode(abbreviated)
+                    .into_vec()
+                    .map_err(|err| malformed_ledger_data(&err.to_string()))?,
+            );
+            Ok(bs58::encode(key).into_string())
+        }
+        None => Ok(verkey.to_string()),
+    }
+}
+
+fn build_service(did: &str, endpoint: &Value) -> Option<Value> {
+    let (service_endpoint, routing_keys, types) = match endpoint {
+        Value::String(url) => (url.clone(), json!([]), json!(["endpoint"])),
+        Value::Object(endpoint) => (
+            endpoint.get("endpoint")?.as_str()?.to_string(),
+            endpoint.get("routingKeys").cloned().unwrap_or(json!([])),
+            endpoint
+                .get("types")
+                .cloned()
+                .unwrap_or(json!(["endpoint"])),
+        ),
+        _ => return None,
+    };
+    Some(json!({
+        "id": format!("{}#endpoint", did),
+        "type": types,
+        "serviceEndpoint": service_endpoint,
+        "routingKeys": routing_keys,
+    }))
+}
+
+fn build_did_json_response(
+    did: &str,
+    did_id: &str,
+    version: &DocumentVersion,
+    version_id: u64,
+    created: Option<i64>,
+    next_version: Option<&DocumentVersion>,
+) -> Result<DIDJsonResponse, DidSovDriverError> {
+    let mut did_document = json!({ "id": did });
+    if let Some(verkey) = version.nym.data["verkey"].as_str() {
+        let verification_method_id = format!("{}#1", did);
+        did_document["verificationMethod"] = json!([{
+            "id": verification_method_id,
+            "type": "Ed25519VerificationKey2018",
+            "controller": did,
+            "publicKeyBase58": full_verkey(did_id, verkey)?,
+        }]);
+        did_document["authentication"] = json!([verification_method_id]);
+    }
+    if let Some(service) = version
+        .endpoint
+        .as_ref()
+        .and_then(|endpoint| build_service(did, &endpoint.data["endpoint"]))
+    {
+        did_document["service"] = json!([service]);
+    }
+
+    let mut did_document_metadata = json!({
+        "versionId": version_id.to_string(),
+        "updated": format_timestamp(version.updated()),
+    });
+    if let Some(created) = created {
+        did_document_metadata["created"] = json!(format_timestamp(created));
+    }
+    if let Some(next_version) = next_version {
+        did_document_metadata["nextVersionId"] = json!(next_version.version_id().to_string());
+        did_document_metadata["nextUpdate"] = json!(format_timestamp(next_version.updated()));
+    }
+
+    Ok(DIDJsonResponse(json!({
+        "didDocument": did_document,
+        "didResolutionMetadata": {},
+        "didDocumentMetadata": did_document_metadata,
+    })))
+}
@@ -0,0 +1,117 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::representation::ContentType;
+use crate::response::DIDJsonResponse;
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Validators and freshness lifetime of a response, sent as HTTP caching headers so that
+/// conditional requests can be answered with 304 Not Modified.
+#[derive(Debug)]
+pub struct CacheValidators {
+    etag: String,
+    last_modified: Option<DateTime<Utc>>,
+    /// Time left until the driver cache expires the response, or `None` if it is not cached.
+    max_age: Option<Duration>,
+}
+
+impl CacheValidators {
+    /// Derives the entity tag from the DID document and its metadata, leaving out the resolution
+    /// metadata, which varies with the request, e.g. with unknown options. The media type is
+    /// included, as each representation is a distinct entity.
+    pub fn new(
+        response: &DIDJsonResponse,
+        content_type: ContentType,
+        last_modified: Option<DateTime<Utc>>,
+        max_age: Option<Duration>,
+    ) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(content_type.media_type().as_bytes());
+        hasher.update(b"\n");
+        hasher.update(response.did_document().to_string().as_bytes());
+        hasher.update(b"\n");
+        hasher.update(response.did_document_metadata().to_string().as_bytes());
+        let digest = hasher.finalize();
+        let etag = digest
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        Self {
+            etag: format!("\"{}\"", etag),
+            last_modified,
+            max_age,
+        }
+    }
+
+    /// Evaluates `If-None-Match` and, in its absence, `If-Modified-Since` as described in
+    /// RFC 9110.
+    pub fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+            return if_none_match.to_str().is_ok_and(|tags| {
+                tags.split(',')
+                    .map(str::trim)
+                    .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == self.etag)
+            });
+        }
+        match (self.last_modified, headers.get(header::IF_MODIFIED_SINCE)) {
+            (Some(last_modified), Some(if_modified_since)) => if_modified_since
+                .to_str()
+                .ok()
+                .and_then(|since| DateTime::parse_from_rfc2822(since).ok())
+                .is_some_and(|since| last_modified.timestamp() <= since.timestamp()),
+            _ => false,
+        }
+    }
+
+    pub fn not_modified(&self) -> Response {
+        let mut res = StatusCode::NOT_MODIFIED.into_response();
+        self.apply(res.headers_mut());
+        res
+    }
+
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        // Weak, as the body may still differ in the resolution metadata
+        if let Ok(etag) = HeaderValue::from_str(&format!("W/{}", self.etag)) {
+            headers.insert(header::ETAG, etag);
+        }
+        if let Some(last_modified) = self.last_modified {
+            if let Ok(last_modified) =
+                HeaderValue::from_str(&last_modified.format(HTTP_DATE_FORMAT).to_string())
+            {
+                headers.insert(header::LAST_MODIFIED, last_modified);
+            }
+        }
+        let cache_control = match self.max_age {
+            Some(max_age) => format!("max-age={}", max_age.as_secs()),
+            None => "no-cache".to_string(),
+        };
+        if let Ok(cache_control) = HeaderValue::from_str(&cache_control) {
+            headers.insert(header::CACHE_CONTROL, cache_control);
+        }
+        // The representation depends on the Accept header
+        headers.insert(header::VARY, HeaderValue::from_static("Accept"));
+    }
+}
@@ -14,9 +14,9 @@
  * limitations under the License.
  */
 
-use std::{num::NonZeroUsize, sync::Arc};
+use std::{collections::HashMap, num::NonZeroUsize, sync::Arc};
 
-use crate::config::Config;
+use crate::config::{Config, NetworkConfig};
 use aries_vcx::{
     aries_vcx_core::{
         indy::{
@@ -29,11 +29,14 @@ use aries_vcx::{
 };
 use did_resolver_sov::resolution::DIDSovResolver;
 
-fn prepare_genesis_path(config: &Config) -> Result<String, anyhow::Error> {
+use crate::history::HistoricalResolver;
+use crate::network::{Network, Networks};
+
+fn prepare_genesis_path(config: &NetworkConfig) -> Result<String, anyhow::Error> {
     let base_path = std::env::current_dir()?;
     let genesis_directory = base_path.join("genesis");
     Ok(genesis_directory
-        .join(format!("{}.txn", config.pool.network))
+        .join(format!("{}.txn", config.genesis))
         .display()
         .to_string())
 }
@@ -48,26 +51,54 @@ async fn create_wallet(config: &Config) -> Result<WalletHandle, anyhow::Error> {
     open_wallet(&config_wallet).await.map_err(|err| err.into())
 }
 
-async fn open_pool(config: &Config, genesis_path: &str) -> Result<PoolHandle, anyhow::Error> {
+async fn open_pool(
+    config: &NetworkConfig,
+    genesis_path: &str,
+) -> Result<PoolHandle, anyhow::Error> {
     let pool_config = PoolConfigBuilder::default()
         .genesis_path(genesis_path)
         .build()?;
-    create_pool_ledger_config(&config.pool.name, genesis_path)?;
-    Ok(open_pool_ledger(&config.pool.name, Some(pool_config)).await?)
+    create_pool_ledger_config(&config.pool_name, genesis_path)?;
+    Ok(open_pool_ledger(&config.pool_name, Some(pool_config)).await?)
 }
 
-pub async fn initialize_resolver_from_config(
-    config: &Config,
-) -> Result<DIDSovResolver, anyhow::Error> {
+async fn initialize_network(
+    config: &NetworkConfig,
+    wallet_handle: WalletHandle,
+    resolver_cache_capacity: Option<NonZeroUsize>,
+) -> Result<Network, anyhow::Error> {
     let genesis_path = prepare_genesis_path(config)?;
-
-    let wallet_handle = create_wallet(config).await?;
     let pool_handle = open_pool(config, &genesis_path).await?;
 
     let profile: Arc<dyn Profile> = Arc::new(VdrtoolsProfile::new(wallet_handle, pool_handle));
 
-    Ok(DIDSovResolver::new(
-        profile.inject_ledger(),
-        NonZeroUsize::new(10).unwrap(),
-    ))
+    let ledger = profile.inject_ledger();
+
+    Ok(Network {
+        name: config.name.clone(),
+        resolver: resolver_cache_capacity
+            .map(|capacity| DIDSovResolver::new(ledger.clone(), capacity)),
+        historical_resolver: HistoricalResolver::new(ledger.clone()),
+        ledger,
+    })
+}
+
+pub async fn initialize_networks_from_config(config: &Config) -> Result<Networks, anyhow::Error> {
+    let wallet_handle = create_wallet(config).await?;
+    // The cache of the resolver is never purged, so it goes along with the cache of the driver
+    let resolver_cache_capacity =
+        NonZeroUsize::new(config.cache.resolver_capacity).filter(|_| config.cache.enabled);
+
+    let mut networks = HashMap::new();
+    for (namespace, network_config) in &config.pool.networks {
+        info!(
+            "Connecting to network {} for namespace {}",
+            network_config.name, namespace
+        );
+        let network =
+            initialize_network(network_config, wallet_handle, resolver_cache_capacity).await?;
+        networks.insert(namespace.clone(), network);
+    }
+
+    Networks::new(networks, config.pool.default_network.clone())
 }
@@ -17,48 +17,110 @@
 #[macro_use]
 extern crate log;
 
+mod admin;
+mod batch;
+mod cache;
+mod coalesce;
 mod config;
+mod dereference;
 mod error;
+mod health;
+mod history;
+mod http_cache;
 mod init;
+mod metrics;
+mod network;
+mod options;
+mod prewarm;
+mod properties;
+mod representation;
 mod resolve;
 mod response;
+mod retry;
+mod state;
+mod telemetry;
 
 use anyhow::Context;
 use axum::Server;
-use axum::{routing::get, Extension, Router};
-use lru::LruCache;
-use response::DIDJsonResponse;
-use std::num::NonZeroUsize;
-use std::time::Instant;
+use axum::{
+    middleware,
+    routing::{get, post},
+    Extension, Router,
+};
 use std::{net::SocketAddr, sync::Arc};
-use tokio::sync::Mutex;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
 use tower_http::trace::TraceLayer;
 
+use crate::batch::resolve_dids;
+use crate::cache::ResolutionCache;
+use crate::coalesce::Coalescer;
 use crate::config::Config;
-use crate::init::initialize_resolver_from_config;
+use crate::error::render_errors;
+use crate::init::initialize_networks_from_config;
+use crate::metrics::{render_metrics, track_requests, Metrics};
+use crate::prewarm::{load_prewarm_dids, prewarm_cache_keys, spawn_prewarming};
+use crate::properties::{methods, properties, DriverProperties};
+use crate::state::AppState;
 use resolve::resolve_did;
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     let config = Config::new()?;
 
-    tracing_subscriber::fmt()
-        .with_max_level(config.application.log_level.0)
-        .init();
+    telemetry::init(&config)?;
 
-    let resolver = initialize_resolver_from_config(&config).await?;
-    let cache =
-        LruCache::<String, (Instant, DIDJsonResponse)>::new(NonZeroUsize::new(100).unwrap());
-    let app = Router::new()
+    let networks = Arc::new(initialize_networks_from_config(&config).await?);
+    let prewarm_dids = load_prewarm_dids(&config.cache.prewarm)?;
+    let pinned_keys = prewarm_cache_keys(&prewarm_dids, &networks)?;
+    let cache = ResolutionCache::new(config.cache.clone(), pinned_keys).await?;
+    let state = AppState {
+        metrics: Arc::new(Metrics::new(cache.clone())?),
+        cache,
+        coalescer: Arc::new(Coalescer::default()),
+        batch: config.batch.clone(),
+        error_format: config.application.error_format,
+        resolution: config.resolution.clone(),
+    };
+    spawn_prewarming(
+        prewarm_dids,
+        &config.cache.prewarm,
+        networks.clone(),
+        state.clone(),
+    )?;
+    let mut app = Router::new()
+        .route("/1.0/identifiers", post(resolve_dids))
         .route("/1.0/identifiers/:did", get(resolve_did))
-        .layer(Extension(Arc::new(resolver)))
-        .layer(TraceLayer::new_for_http())
-        .with_state(Arc::new(Mutex::new(cache)));
+        .route("/1.0/properties", get(properties))
+        .route("/1.0/methods", get(methods))
+        .route("/health/live", get(health::live))
+        .route("/health/ready", get(health::ready))
+        .route("/metrics", get(render_metrics));
+    match config.admin.token.clone() {
+        Some(token) => app = app.merge(admin::router(token)),
+        None => info!("Admin endpoints are disabled, no admin token is configured"),
+    }
+    let app = app
+        .layer(Extension(networks))
+        .layer(Extension(Arc::new(DriverProperties::new(&config))))
+        .layer(middleware::from_fn_with_state(state.clone(), render_errors))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            track_requests,
+        ))
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::make_request_span))
+        .layer(PropagateRequestIdLayer::new(telemetry::REQUEST_ID_HEADER))
+        .layer(SetRequestIdLayer::new(
+            telemetry::REQUEST_ID_HEADER,
+            MakeRequestUuid,
+        ))
+        .with_state(state);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], config.application.port));
     info!("Server listening on http://{}", addr);
-    Server::bind(&addr)
+    let result = Server::bind(&addr)
         .serve(app.into_make_service())
         .await
-        .context("Server failed")
+        .context("Server failed");
+    telemetry::shutdown();
+    result
 }
@@ -0,0 +1,231 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use axum::{
+    extract::State,
+    http::{header, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+use prometheus::{
+    core::{Collector, Desc, Describer},
+    proto::MetricFamily,
+    Encoder, HistogramOpts, HistogramTimer, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    Opts, Registry, TextEncoder,
+};
+
+use crate::cache::ResolutionCache;
+use crate::error::ErrorKind;
+use crate::state::AppState;
+
+const NAMESPACE: &str = "driver_did_sov";
+
+fn opts(name: &str, help: &str) -> Opts {
+    Opts::new(name, help).namespace(NAMESPACE)
+}
+
+/// How a resolution was served with respect to the cache.
+#[derive(Debug, Clone, Copy)]
+pub enum CacheOutcome {
+    Hit,
+    Stale,
+    Miss,
+}
+
+impl CacheOutcome {
+    fn label(&self) -> &'static str {
+        match self {
+            CacheOutcome::Hit => "hit",
+            CacheOutcome::Stale => "stale",
+            CacheOutcome::Miss => "miss",
+        }
+    }
+}
+
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    requests_in_flight: IntGauge,
+    resolution_duration: HistogramVec,
+    ledger_read_duration: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new(cache: Arc<ResolutionCache>) -> Result<Self, prometheus::Error> {
+        let histogram_opts =
+            |name: &str, help: &str| HistogramOpts::new(name, help).namespace(NAMESPACE);
+        let metrics = Self {
+            registry: Registry::new(),
+            requests: IntCounterVec::new(
+                opts(
+                    "http_requests_total",
+                    "HTTP requests by status code and error kind",
+                ),
+                &["status", "error"],
+            )?,
+            requests_in_flight: IntGauge::with_opts(opts(
+                "http_requests_in_flight",
+                "HTTP requests being served",
+            ))?,
+            resolution_duration: HistogramVec::new(
+                histogram_opts(
+                    "resolution_duration_seconds",
+                    "Duration of DID resolutions by cache outcome",
+                ),
+                &["cache"],
+            )?,
+            ledger_read_duration: HistogramVec::new(
+                histogram_opts(
+                    "ledger_read_duration_seconds",
+                    "Duration of reads of DIDs from the ledger by network",
+                ),
+                &["network"],
+            )?,
+        };
+        let collectors: [Box<dyn Collector>; 5] = [
+            Box::new(metrics.requests.clone()),
+            Box::new(metrics.requests_in_flight.clone()),
+            Box::new(metrics.resolution_duration.clone()),
+            Box::new(metrics.ledger_read_duration.clone()),
+            Box::new(CacheCollector::new(cache)?),
+        ];
+        for collector in collectors {
+            metrics.registry.register(collector)?;
+        }
+        Ok(metrics)
+    }
+
+    pub fn observe_resolution(&self, outcome: CacheOutcome, seconds: f64) {
+        self.resolution_duration
+            .with_label_values(&[outcome.label()])
+            .observe(seconds);
+    }
+
+    /// Times a read from the ledger until the returned timer is dropped, including when the read
+    /// is cancelled.
+    pub fn time_ledger_read(&self, network: &str) -> HistogramTimer {
+        self.ledger_read_duration
+            .with_label_values(&[network])
+            .start_timer()
+    }
+}
+
+/// Exposes the statistics kept by the cache as they are at the time of each scrape.
+struct CacheCollector {
+    cache: Arc<ResolutionCache>,
+    entries: Opts,
+    hits: Opts,
+    misses: Opts,
+    evictions: Opts,
+    descs: Vec<Desc>,
+}
+
+impl CacheCollector {
+    fn new(cache: Arc<ResolutionCache>) -> Result<Self, prometheus::Error> {
+        let entries = opts("cache_entries", "Cached resolutions");
+        let hits = opts("cache_hits_total", "Cache hits");
+        let misses = opts("cache_misses_total", "Cache misses");
+        let evictions = opts(
+            "cache_evictions_total",
+            "Cache entries evicted to stay within capacity",
+        );
+        let descs = [&entries, &hits, &misses, &evictions]
+            .into_iter()
+            .map(Describer::describe)
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            cache,
+            entries,
+            hits,
+            misses,
+            evictions,
+            descs,
+        })
+    }
+}
+
+impl Collector for CacheCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.descs.iter().collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let stats = self.cache.stats();
+        let mut families = Vec::new();
+        // The options were validated when describing them
+        if let Ok(entries) = IntGauge::with_opts(self.entries.clone()) {
+            entries.set(stats.size as i64);
+            families.extend(entries.collect());
+        }
+        for (opts, value) in [
+            (&self.hits, stats.hits),
+            (&self.misses, stats.misses),
+            (&self.evictions, stats.evictions),
+        ] {
+            if let Ok(counter) = IntCounter::with_opts(opts.clone()) {
+                counter.inc_by(value);
+                families.extend(counter.collect());
+            }
+        }
+        families
+    }
+}
+
+/// Decrements the in-flight gauge when a request completes or is cancelled.
+struct InFlightGuard<'a>(&'a IntGauge);
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+pub async fn track_requests<B>(
+    State(state): State<AppState>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let metrics = &state.metrics;
+    metrics.requests_in_flight.inc();
+    let _guard = InFlightGuard(&metrics.requests_in_flight);
+
+    let response = next.run(request).await;
+    let error = response
+        .extensions()
+        .get::<ErrorKind>()
+        .map_or("none", |kind| kind.0);
+    metrics
+        .requests
+        .with_label_values(&[response.status().as_str(), error])
+        .inc();
+    response
+}
+
+pub async fn render_metrics(State(state): State<AppState>) -> Response {
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    if let Err(err) = encoder.encode(&state.metrics.registry.gather(), &mut body) {
+        error!("Failed to encode metrics: {}", err);
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    let mut res = body.into_response();
+    if let Ok(content_type) = HeaderValue::from_str(encoder.format_type()) {
+        res.headers_mut().insert(header::CONTENT_TYPE, content_type);
+    }
+    res
+}
@@ -0,0 +1,103 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use aries_vcx::aries_vcx_core::ledger::base_ledger::BaseLedger;
+use did_resolver_sov::{error::DIDSovError, resolution::DIDSovResolver};
+
+use crate::error::DidSovDriverError;
+use crate::history::HistoricalResolver;
+
+const DID_SOV_PREFIX: &str = "did:sov:";
+
+/// A ledger the driver resolves DIDs against.
+pub struct Network {
+    pub name: String,
+    pub ledger: Arc<dyn BaseLedger>,
+    /// Resolver of the latest versions of DIDs, caching ledger responses, if enabled.
+    pub resolver: Option<DIDSovResolver>,
+    pub historical_resolver: HistoricalResolver,
+}
+
+/// A DID routed to the network it is anchored on.
+pub struct RoutedDid<'a> {
+    pub namespace: &'a str,
+    pub network: &'a Network,
+    /// The DID without its network namespace, as known to the ledger.
+    pub did: String,
+}
+
+impl RoutedDid<'_> {
+    /// The DID with the namespace of its network, however the namespace was written, e.g.
+    /// `did:sov:staging:WRfXPg8dantKVubE3HX8pw`. Resolutions are cached under it.
+    pub fn canonical_did(&self) -> String {
+        match self.did.strip_prefix(DID_SOV_PREFIX) {
+            Some(id) => format!("{}{}:{}", DID_SOV_PREFIX, self.namespace, id),
+            None => self.did.clone(),
+        }
+    }
+}
+
+/// The configured networks, keyed by the did:sov namespace which selects them.
+pub struct Networks {
+    networks: HashMap<String, Network>,
+    default_namespace: String,
+}
+
+impl Networks {
+    pub fn new(
+        networks: HashMap<String, Network>,
+        default_namespace: String,
+    ) -> Result<Self, anyhow::Error> {
+        if !networks.contains_key(&default_namespace) {
+            anyhow::bail!(
+                "Default network {} is not among the configured networks",
+                default_namespace
+            );
+        }
+        Ok(Self {
+            networks,
+            default_namespace,
+        })
+    }
+
+    /// The networks keyed by namespace.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Network)> {
+        self.networks.iter()
+    }
+
+    /// Picks the network of a DID from its namespace, e.g. `did:sov:staging:WRfXPg8dantKVubE3HX8pw`.
+    /// DIDs without a namespace, and DIDs of other methods, are routed to the default network.
+    pub fn route(&self, did: &str) -> Result<RoutedDid, DidSovDriverError> {
+        let (namespace, did) = match did.strip_prefix(DID_SOV_PREFIX) {
+            Some(method_specific_id) => match method_specific_id.rsplit_once(':') {
+                Some((namespace, id)) => (namespace, format!("{}{}", DID_SOV_PREFIX, id)),
+                None => (self.default_namespace.as_str(), did.to_string()),
+            },
+            None => (self.default_namespace.as_str(), did.to_string()),
+        };
+        let (namespace, network) = self.networks.get_key_value(namespace).ok_or_else(|| {
+            DIDSovError::InvalidDID(format!("Unknown did:sov network namespace {}", namespace))
+        })?;
+        Ok(RoutedDid {
+            namespace,
+            network,
+            did,
+        })
+    }
+}
@@ -0,0 +1,161 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use axum::http::{header::ACCEPT, HeaderMap};
+use chrono::{DateTime, Utc};
+use did_resolver_sov::did_resolver::{
+    shared_types::media_type::MediaType,
+    traits::resolvable::resolution_options::DIDResolutionOptions,
+};
+
+use crate::error::DidSovDriverError;
+use crate::representation::ContentType;
+
+const ACCEPT_OPTION: &str = "accept";
+const NO_CACHE_OPTION: &str = "noCache";
+const VERSION_ID_OPTION: &str = "versionId";
+const VERSION_TIME_OPTION: &str = "versionTime";
+const TIMEOUT_OPTION: &str = "timeout";
+
+/// Selects a historical version of a DID document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionSelector {
+    /// Sequence number of the ledger transaction which produced the version.
+    Id(u64),
+    /// The version in effect at the given time.
+    Time(DateTime<Utc>),
+}
+
+/// DID resolution options supplied by the client as query parameters.
+#[derive(Debug, Clone, Default)]
+pub struct ResolutionParams {
+    pub accept: Option<String>,
+    pub no_cache: bool,
+    pub version: Option<VersionSelector>,
+    /// Deadline of the resolution requested by the client.
+    pub timeout: Option<Duration>,
+    pub unknown: Vec<String>,
+}
+
+impl ResolutionParams {
+    pub fn from_query(query: HashMap<String, String>) -> Result<Self, DidSovDriverError> {
+        let mut params = ResolutionParams::default();
+        for (name, value) in query {
+            if !params.apply_option(&name, value)? {
+                params.unknown.push(name);
+            }
+        }
+        params.unknown.sort();
+        Ok(params)
+    }
+
+    /// Returns `false` if the option is not recognized.
+    pub fn apply_option(&mut self, name: &str, value: String) -> Result<bool, DidSovDriverError> {
+        match name {
+            ACCEPT_OPTION => self.accept = Some(value),
+            NO_CACHE_OPTION => self.no_cache = value.eq_ignore_ascii_case("true"),
+            VERSION_ID_OPTION => {
+                let version_id = value.parse().map_err(|_| {
+                    DidSovDriverError::InvalidOptions(format!(
+                        "{} must be a ledger sequence number, got {}",
+                        VERSION_ID_OPTION, value
+                    ))
+                })?;
+                self.set_version(VersionSelector::Id(version_id))?;
+            }
+            VERSION_TIME_OPTION => {
+                let version_time = DateTime::parse_from_rfc3339(&value).map_err(|_| {
+                    DidSovDriverError::InvalidOptions(format!(
+                        "{} must be an ISO 8601 timestamp, got {}",
+                        VERSION_TIME_OPTION, value
+                    ))
+                })?;
+                self.set_version(VersionSelector::Time(version_time.with_timezone(&Utc)))?;
+            }
+            TIMEOUT_OPTION => {
+                let timeout_ms = value
+                    .parse()
+                    .ok()
+                    .filter(|timeout_ms| *timeout_ms > 0)
+                    .ok_or_else(|| {
+                        DidSovDriverError::InvalidOptions(format!(
+                            "{} must be a positive number of milliseconds, got {}",
+                            TIMEOUT_OPTION, value
+                        ))
+                    })?;
+                self.timeout = Some(Duration::from_millis(timeout_ms));
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn set_version(&mut self, version: VersionSelector) -> Result<(), DidSovDriverError> {
+        if self.version.is_some() {
+            return Err(DidSovDriverError::InvalidOptions(format!(
+                "{} and {} are mutually exclusive",
+                VERSION_ID_OPTION, VERSION_TIME_OPTION
+            )));
+        }
+        self.version = Some(version);
+        Ok(())
+    }
+
+    /// Historical versions are cached separately from the latest version of a DID.
+    pub fn cache_key(&self, did: &str) -> String {
+        match self.version {
+            Some(VersionSelector::Id(version_id)) => {
+                format!("{}?{}={}", did, VERSION_ID_OPTION, version_id)
+            }
+            Some(VersionSelector::Time(version_time)) => format!(
+                "{}?{}={}",
+                did,
+                VERSION_TIME_OPTION,
+                version_time.timestamp()
+            ),
+            None => did.to_string(),
+        }
+    }
+
+    /// The `accept` option takes precedence over the `Accept` header.
+    pub fn content_type(&self, headers: &HeaderMap) -> Result<ContentType, DidSovDriverError> {
+        let accept = match &self.accept {
+            Some(accept) => Some(accept.as_str()),
+            None => headers
+                .get(ACCEPT)
+                .map(|accept| accept.to_str())
+                .transpose()
+                .map_err(|_| {
+                    DidSovDriverError::RepresentationNotSupported(
+                        "Accept header is not valid ASCII".to_string(),
+                    )
+                })?,
+        };
+        ContentType::negotiate(accept)
+    }
+
+    /// The resolver always produces the plain JSON document; JSON-LD and the resolution result
+    /// envelope are rendered by the driver, so an explicit `accept` is forwarded as `DidJson`.
+    pub fn to_resolution_options(&self) -> DIDResolutionOptions {
+        match self.accept {
+            Some(_) => DIDResolutionOptions::new().set_accept(MediaType::DidJson),
+            None => DIDResolutionOptions::default(),
+        }
+    }
+}
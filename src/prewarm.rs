@@ -0,0 +1,97 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+
+use crate::config::PrewarmConfig;
+use crate::network::Networks;
+use crate::options::ResolutionParams;
+use crate::resolve::refresh_did;
+use crate::state::AppState;
+
+/// Collects the DIDs to prewarm from the configuration and the file it refers to. The file lists
+/// one DID per line, blank lines and lines starting with `#` are ignored.
+pub fn load_prewarm_dids(config: &PrewarmConfig) -> Result<Vec<String>, anyhow::Error> {
+    let mut dids = config.dids.clone();
+    if let Some(file) = &config.file {
+        let contents = std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read DIDs to prewarm from {}", file))?;
+        dids.extend(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string),
+        );
+    }
+    dids.sort();
+    dids.dedup();
+    Ok(dids)
+}
+
+/// The cache keys under which the results of the prewarmed DIDs are kept.
+pub fn prewarm_cache_keys(
+    dids: &[String],
+    networks: &Networks,
+) -> Result<HashSet<String>, anyhow::Error> {
+    let params = ResolutionParams::default();
+    dids.iter()
+        .map(|did| {
+            let routed_did = networks
+                .route(did)
+                .map_err(|err| anyhow::anyhow!("Cannot prewarm DID {}: {}", did, err))?;
+            Ok(params.cache_key(&routed_did.canonical_did()))
+        })
+        .collect()
+}
+
+/// Resolves the DIDs right away and then again every `refresh_interval_secs`, keeping them in
+/// the cache.
+pub fn spawn_prewarming(
+    dids: Vec<String>,
+    config: &PrewarmConfig,
+    networks: Arc<Networks>,
+    state: AppState,
+) -> Result<(), anyhow::Error> {
+    if dids.is_empty() {
+        return Ok(());
+    }
+    if config.refresh_interval_secs == 0 {
+        anyhow::bail!("Prewarm refresh interval must be positive");
+    }
+    let refresh_interval = Duration::from_secs(config.refresh_interval_secs);
+    info!(
+        "Prewarming the cache with {} DIDs every {:?}",
+        dids.len(),
+        refresh_interval
+    );
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(refresh_interval);
+        loop {
+            interval.tick().await;
+            for did in &dids {
+                if let Err(err) = refresh_did(did, &networks, &state).await {
+                    error!("Failed to prewarm the cache with DID {}: {}", did, err);
+                }
+            }
+        }
+    });
+    Ok(())
+}
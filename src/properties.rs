@@ -0,0 +1,92 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use axum::{Extension, Json};
+use serde_json::{json, Map, Value};
+
+use crate::config::Config;
+use crate::representation::{
+    DID_JSON_MEDIA_TYPE, DID_LD_JSON_MEDIA_TYPE, DID_RESOLUTION_MEDIA_TYPE,
+    DID_URL_DEREFERENCING_MEDIA_TYPE,
+};
+
+const DID_METHODS: [&str; 1] = ["sov"];
+
+/// Capabilities of the driver as reported to the Universal Resolver.
+pub struct DriverProperties(Value);
+
+impl DriverProperties {
+    /// Describes the configuration, leaving out secrets and local paths.
+    pub fn new(config: &Config) -> Self {
+        let networks = config
+            .pool
+            .networks
+            .iter()
+            .map(|(namespace, network)| (namespace.clone(), json!({ "name": network.name })))
+            .collect::<Map<_, _>>();
+        let cache = &config.cache;
+        let cache_networks = cache
+            .networks
+            .iter()
+            .map(|(namespace, overrides)| {
+                (
+                    namespace.clone(),
+                    json!({
+                        "enabled": overrides.enabled,
+                        "ttlSecs": overrides.ttl_secs,
+                        "negativeTtlSecs": overrides.negative_ttl_secs,
+                    }),
+                )
+            })
+            .collect::<Map<_, _>>();
+        Self(json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "methods": DID_METHODS,
+            "defaultNetwork": config.pool.default_network,
+            "networks": networks,
+            "contentTypes": [
+                DID_RESOLUTION_MEDIA_TYPE,
+                DID_URL_DEREFERENCING_MEDIA_TYPE,
+                DID_LD_JSON_MEDIA_TYPE,
+                DID_JSON_MEDIA_TYPE,
+            ],
+            "resolution": {
+                "timeoutMs": config.resolution.timeout_ms,
+                "maxTimeoutMs": config.resolution.max_timeout_ms,
+            },
+            "cache": {
+                "enabled": cache.enabled,
+                "backend": cache.backend,
+                "capacity": cache.capacity,
+                "ttlSecs": cache.ttl_secs,
+                "negativeTtlSecs": cache.negative_ttl_secs,
+                "staleWhileRevalidate": cache.stale_while_revalidate,
+                "maxStaleSecs": cache.max_stale_secs,
+                "networks": cache_networks,
+            },
+        }))
+    }
+}
+
+pub async fn properties(Extension(properties): Extension<Arc<DriverProperties>>) -> Json<Value> {
+    Json(properties.0.clone())
+}
+
+pub async fn methods() -> Json<Value> {
+    Json(json!(DID_METHODS))
+}
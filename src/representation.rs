@@ -0,0 +1,132 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::error::DidSovDriverError;
+
+pub const DID_RESOLUTION_MEDIA_TYPE: &str =
+    "application/ld+json;profile=\"https://w3id.org/did-resolution\"";
+pub const DID_URL_DEREFERENCING_MEDIA_TYPE: &str =
+    "application/ld+json;profile=\"https://w3id.org/did-url-dereferencing\"";
+pub const DID_LD_JSON_MEDIA_TYPE: &str = "application/did+ld+json";
+pub const DID_JSON_MEDIA_TYPE: &str = "application/did+json";
+
+const DID_RESOLUTION_PROFILE: &str = "https://w3id.org/did-resolution";
+const DID_URL_DEREFERENCING_PROFILE: &str = "https://w3id.org/did-url-dereferencing";
+
+/// Representation of a resolution result requested by the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    /// The full DID Resolution Result, including resolution and document metadata.
+    DidResolution,
+    /// The full DID URL Dereferencing Result, including dereferencing and content metadata.
+    DidUrlDereferencing,
+    /// The bare DID document in its JSON-LD representation.
+    DidLdJson,
+    /// The bare DID document in its plain JSON representation.
+    DidJson,
+}
+
+impl ContentType {
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            ContentType::DidResolution => DID_RESOLUTION_MEDIA_TYPE,
+            ContentType::DidUrlDereferencing => DID_URL_DEREFERENCING_MEDIA_TYPE,
+            ContentType::DidLdJson => DID_LD_JSON_MEDIA_TYPE,
+            ContentType::DidJson => DID_JSON_MEDIA_TYPE,
+        }
+    }
+
+    /// Picks the representation preferred by the given `Accept` value. A missing value or a
+    /// wildcard falls back to the full DID Resolution Result.
+    pub fn negotiate(accept: Option<&str>) -> Result<Self, DidSovDriverError> {
+        let accept = match accept {
+            Some(accept) => accept,
+            None => return Ok(ContentType::DidResolution),
+        };
+        if accept.trim().is_empty() {
+            return Ok(ContentType::DidResolution);
+        }
+
+        // Ties in quality are broken in favour of explicit media types over wildcards.
+        let mut best: Option<(f32, bool, ContentType)> = None;
+        for media_range in accept.split(',') {
+            let (content_type, quality, explicit) = match parse_media_range(media_range) {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+            if quality <= 0.0 {
+                continue;
+            }
+            let is_better = match best {
+                Some((best_quality, best_explicit, _)) => {
+                    quality > best_quality
+                        || (quality == best_quality && explicit && !best_explicit)
+                }
+                None => true,
+            };
+            if is_better {
+                best = Some((quality, explicit, content_type));
+            }
+        }
+
+        best.map(|(_, _, content_type)| content_type)
+            .ok_or_else(|| {
+                DidSovDriverError::RepresentationNotSupported(format!(
+                    "None of the requested media types are supported: {}",
+                    accept
+                ))
+            })
+    }
+}
+
+fn parse_media_range(media_range: &str) -> Option<(ContentType, f32, bool)> {
+    let mut parts = media_range.split(';').map(str::trim);
+    let essence = parts.next()?.to_ascii_lowercase();
+
+    let mut quality = 1.0;
+    let mut profile = None;
+    for param in parts {
+        let (name, value) = match param.split_once('=') {
+            Some((name, value)) => (name.trim().to_ascii_lowercase(), value.trim()),
+            None => continue,
+        };
+        match name.as_str() {
+            "q" => quality = value.parse().unwrap_or(0.0),
+            "profile" => profile = Some(value.trim_matches('"').to_string()),
+            _ => {}
+        }
+    }
+
+    let explicit = !essence.ends_with("/*");
+    let content_type = match essence.as_str() {
+        "application/ld+json" => {
+            let profile = profile?;
+            let mut profiles = profile.split_whitespace();
+            if profiles.clone().any(|uri| uri == DID_RESOLUTION_PROFILE) {
+                ContentType::DidResolution
+            } else if profiles.any(|uri| uri == DID_URL_DEREFERENCING_PROFILE) {
+                ContentType::DidUrlDereferencing
+            } else {
+                return None;
+            }
+        }
+        "application/did+ld+json" => ContentType::DidLdJson,
+        "application/did+json" => ContentType::DidJson,
+        "*/*" | "application/*" => ContentType::DidResolution,
+        _ => return None,
+    };
+    Some((content_type, quality, explicit))
+}
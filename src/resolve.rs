@@ -1,39 +1,37 @@
-use axum::extract::State;
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
 use axum::{extract::Path, Extension};
+use did_resolver_sov::did_resolver::did_parser::ParsedDID;
 use did_resolver_sov::did_resolver::traits::resolvable::resolution_output::DIDResolutionOutput;
 use did_resolver_sov::did_resolver::traits::resolvable::DIDResolvable;
-use did_resolver_sov::did_resolver::{
-    did_parser::ParsedDID, traits::resolvable::resolution_options::DIDResolutionOptions,
-};
 use did_resolver_sov::resolution::DIDSovResolver;
-use lru::LruCache;
 use serde_json::json;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::Mutex;
+use tracing::Instrument;
 
+use crate::cache::{CacheLookup, CachedResolution, ResolutionCache};
+use crate::dereference::{dereference, DidUrl};
 use crate::error::DidSovDriverError;
-use crate::response::DIDJsonResponse;
+use crate::http_cache::CacheValidators;
+use crate::metrics::CacheOutcome;
+use crate::network::{Networks, RoutedDid};
+use crate::options::ResolutionParams;
+use crate::representation::ContentType;
+use crate::response::{DIDJsonResponse, DIDRepresentation, DRIVER_METADATA_KEY};
+use crate::retry::with_retries;
+use crate::state::AppState;
 
+#[tracing::instrument(name = "cache_lookup", skip_all, fields(key = %cache_key))]
 async fn is_cached(
-    cache: &Arc<Mutex<LruCache<String, (Instant, DIDJsonResponse)>>>,
-    did: &str,
-) -> Option<DIDJsonResponse> {
-    let cache_ttl = Duration::from_secs(60);
-
-    let mut cache_lock = cache.lock().await;
-    match cache_lock.get_mut(did) {
-        Some((instant, response)) if instant.elapsed() < cache_ttl => {
-            debug!("Cache hit for DID {}", did);
-            Some(response.clone())
-        }
-        Some((_, _)) => {
-            debug!("Cache expired for DID {}", did);
-            cache_lock.pop(did);
-            None
-        }
-        None => None,
-    }
+    cache: &Arc<ResolutionCache>,
+    routed_did: &RoutedDid<'_>,
+    cache_key: &str,
+) -> Option<CacheLookup> {
+    cache.get(routed_did.namespace, cache_key).await
 }
 
 async fn build_did_json_response(resolution_output: DIDResolutionOutput) -> DIDJsonResponse {
@@ -44,38 +42,304 @@ async fn build_did_json_response(resolution_output: DIDResolutionOutput) -> DIDJ
     }))
 }
 
+#[tracing::instrument(
+    name = "ledger_read",
+    skip_all,
+    fields(did = %did, network = %routed_did.network.name)
+)]
 async fn resolve_did_without_cache(
-    did: String,
-    resolver: &Arc<DIDSovResolver>,
+    did: &str,
+    routed_did: &RoutedDid<'_>,
+    params: &ResolutionParams,
 ) -> Result<DIDJsonResponse, DidSovDriverError> {
-    let parsed_did = ParsedDID::parse(did)?;
-    let resolution_output = resolver
-        .resolve(&parsed_did, &DIDResolutionOptions::default())
-        .await?;
+    let network = routed_did.network;
+    debug!("Resolving DID {} on network {}", did, network.name);
 
-    Ok(build_did_json_response(resolution_output).await)
+    let parsed_did = ParsedDID::parse(routed_did.did.clone())?;
+    let response = match params.version {
+        Some(version) => {
+            network
+                .historical_resolver
+                .resolve(&parsed_did, version)
+                .await?
+        }
+        None => {
+            let options = params.to_resolution_options();
+            let resolution_output = match &network.resolver {
+                Some(resolver) => resolver.resolve(&parsed_did, &options).await?,
+                // A resolver of its own for each resolution, so that every one reads the ledger
+                None => {
+                    DIDSovResolver::new(network.ledger.clone(), NonZeroUsize::new(1).unwrap())
+                        .resolve(&parsed_did, &options)
+                        .await?
+                }
+            };
+            build_did_json_response(resolution_output).await
+        }
+    };
+    Ok(response)
+}
+
+/// Writes the DID in a resolution result the way the client did, as results are shared by all
+/// the ways of writing the DID, and carry the DID as known to the ledger.
+fn as_requested(
+    mut response: DIDJsonResponse,
+    routed_did: &RoutedDid<'_>,
+    did: &str,
+) -> DIDJsonResponse {
+    if routed_did.did != did {
+        response.replace_did(&routed_did.did, did);
+    }
+    response
 }
 
 async fn handle_cache(
-    cache: &Arc<Mutex<LruCache<String, (Instant, DIDJsonResponse)>>>,
+    cache: &Arc<ResolutionCache>,
+    routed_did: &RoutedDid<'_>,
+    cache_key: String,
+    resolution: CachedResolution,
+) {
+    cache.put(routed_did.namespace, cache_key, resolution).await;
+}
+
+pub fn finalize_response(
+    mut response: DIDJsonResponse,
+    params: &ResolutionParams,
+    content_type: ContentType,
+) -> DIDRepresentation {
+    if !params.unknown.is_empty() {
+        response.insert_resolution_metadata("unknownOptions", json!(params.unknown));
+    }
+    response.into_representation(content_type)
+}
+
+/// Reads the DID from the ledger, retrying transient failures, and caches the outcome. Returns
+/// the outcome along with the number of retries. Gives up once the deadline of shared reads
+/// passes, as there may be no request left to wait for the outcome.
+async fn read_and_cache(
+    did: &str,
+    networks: &Networks,
+    cache_key: &str,
+    params: &ResolutionParams,
+    state: &AppState,
+) -> (Result<DIDJsonResponse, DidSovDriverError>, u32) {
+    let routed_did = match networks.route(did) {
+        Ok(routed_did) => routed_did,
+        Err(err) => return (Err(err), 0),
+    };
+    let routed_did = &routed_did;
+    let deadline = state.resolution.shared_deadline();
+    let read = with_retries(
+        &state.resolution.retry,
+        &format!("Reading {} from the ledger", did),
+        || async move {
+            // Observed on drop, so that reads cut short by the deadline are observed as well
+            let _timer = state.metrics.time_ledger_read(&routed_did.network.name);
+            resolve_did_without_cache(did, routed_did, params)
+                .await
+                .map_err(DidSovDriverError::classify)
+        },
+    );
+    let (result, retries) = match tokio::time::timeout(deadline, read).await {
+        Ok(outcome) => outcome,
+        Err(_) => {
+            warn!(
+                "Read of {} from the ledger did not complete within {} ms",
+                did,
+                deadline.as_millis()
+            );
+            let err = DidSovDriverError::LedgerTimeout(format!(
+                "Read from the ledger did not complete within {} ms",
+                deadline.as_millis()
+            ));
+            return (Err(err), 0);
+        }
+    };
+    match &result {
+        Ok(response) => {
+            handle_cache(
+                &state.cache,
+                routed_did,
+                cache_key.to_string(),
+                Ok(response.clone()),
+            )
+            .await
+        }
+        Err(err) if err.is_cacheable() => {
+            handle_cache(
+                &state.cache,
+                routed_did,
+                cache_key.to_string(),
+                Err(err.to_cached()),
+            )
+            .await
+        }
+        Err(_) => {}
+    }
+    (result, retries)
+}
+
+async fn resolve_and_cache(
+    did: &str,
+    networks: &Arc<Networks>,
+    cache_key: &str,
+    params: &ResolutionParams,
+    state: &AppState,
+) -> Result<DIDJsonResponse, DidSovDriverError> {
+    let read = {
+        let did = did.to_string();
+        let networks = networks.clone();
+        let cache_key = cache_key.to_string();
+        let params = params.clone();
+        let state = state.clone();
+        async move { read_and_cache(&did, &networks, &cache_key, &params, &state).await }
+            .in_current_span()
+    };
+    // The read goes on in the background when the deadline of the request passes, for the
+    // requests sharing it and for the cache
+    let deadline = state.resolution.deadline(params.timeout);
+    let (result, retries) = tokio::time::timeout(deadline, state.coalescer.run(cache_key, read))
+        .await
+        .map_err(|_| {
+            warn!(
+                "Resolution of {} did not complete within {} ms",
+                did,
+                deadline.as_millis()
+            );
+            DidSovDriverError::LedgerTimeout(format!(
+                "Resolution did not complete within {} ms",
+                deadline.as_millis()
+            ))
+        })?;
+    // Retries are reported only to the request which read the ledger, and are not cached. Reads
+    // which were not retried report nothing, so that their body matches the cached one
+    match (result, retries) {
+        (Ok(mut response), Some(retries)) if retries > 0 => {
+            response.insert_resolution_metadata(DRIVER_METADATA_KEY, json!({ "retries": retries }));
+            Ok(response)
+        }
+        (Err(err), Some(retries)) if retries > 0 => Err(DidSovDriverError::Retried {
+            error: Box::new(err),
+            retries,
+        }),
+        (result, _) => result,
+    }
+}
+
+/// Refreshes a stale cache entry in the background. Should the refresh fail with an error which
+/// is not cached, the stale entry keeps being served until it exceeds the max-stale bound. Logs of
+/// the refresh are attributed to the request which triggered it.
+fn spawn_revalidation(
     did: String,
-    response: DIDJsonResponse,
+    params: ResolutionParams,
+    networks: Arc<Networks>,
+    state: AppState,
 ) {
-    cache.lock().await.put(did, (Instant::now(), response));
+    let revalidation = async move {
+        let cache_key = params.cache_key(&did);
+        if let Err(err) = resolve_and_cache(&did, &networks, &cache_key, &params, &state).await {
+            error!("Failed to revalidate cached resolution of {}: {}", did, err);
+        }
+    };
+    tokio::spawn(revalidation.in_current_span());
 }
 
-pub async fn resolve_did(
-    Path(did): Path<String>,
-    Extension(resolver): Extension<Arc<DIDSovResolver>>,
-    State(cache): State<Arc<Mutex<LruCache<String, (Instant, DIDJsonResponse)>>>>,
+/// Resolves the DID from the ledger regardless of the cache, replacing its cached outcome.
+/// Returns the result with the DID as known to the ledger.
+pub async fn refresh_did(
+    did: &str,
+    networks: &Arc<Networks>,
+    state: &AppState,
 ) -> Result<DIDJsonResponse, DidSovDriverError> {
-    if let Some(response) = is_cached(&cache, &did).await {
-        return Ok(response);
+    let canonical_did = networks.route(did)?.canonical_did();
+    let params = ResolutionParams::default();
+    let cache_key = params.cache_key(&canonical_did);
+    resolve_and_cache(&canonical_did, networks, &cache_key, &params, state).await
+}
+
+/// Resolves the DID, returning the result along with the time it stays fresh in the cache.
+pub async fn resolve_did_with_cache(
+    did: String,
+    params: &ResolutionParams,
+    networks: &Arc<Networks>,
+    state: &AppState,
+) -> Result<(DIDJsonResponse, Option<Duration>), DidSovDriverError> {
+    let started = Instant::now();
+    let routed_did = networks.route(&did)?;
+    let canonical_did = routed_did.canonical_did();
+    let cache_key = params.cache_key(&canonical_did);
+    let observe = |outcome| {
+        state
+            .metrics
+            .observe_resolution(outcome, started.elapsed().as_secs_f64())
+    };
+
+    if !params.no_cache {
+        match is_cached(&state.cache, &routed_did, &cache_key).await {
+            Some(CacheLookup::Fresh(resolution, remaining)) => {
+                observe(CacheOutcome::Hit);
+                return resolution
+                    .map(|response| (as_requested(response, &routed_did, &did), Some(remaining)))
+                    .map_err(DidSovDriverError::Cached);
+            }
+            Some(CacheLookup::Stale(response)) => {
+                observe(CacheOutcome::Stale);
+                spawn_revalidation(
+                    canonical_did,
+                    params.clone(),
+                    networks.clone(),
+                    state.clone(),
+                );
+                return Ok((
+                    as_requested(response, &routed_did, &did),
+                    Some(Duration::ZERO),
+                ));
+            }
+            None => {}
+        }
     }
 
-    let response = resolve_did_without_cache(did.clone(), &resolver).await?;
+    let result = resolve_and_cache(&canonical_did, networks, &cache_key, params, state).await;
+    observe(CacheOutcome::Miss);
+    Ok((
+        as_requested(result?, &routed_did, &did),
+        state.cache.ttl(routed_did.namespace, false),
+    ))
+}
+
+pub async fn resolve_did(
+    Path(did): Path<String>,
+    Query(mut query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    Extension(networks): Extension<Arc<Networks>>,
+    State(state): State<AppState>,
+) -> Result<Response, DidSovDriverError> {
+    let (params, content_type, did_url) = tracing::info_span!("parse").in_scope(|| {
+        let mut did_url = DidUrl::parse(&did);
+        did_url.take_dereferencing_parameters(&mut query)?;
 
-    handle_cache(&cache, did.clone(), response.clone()).await;
+        let mut params = ResolutionParams::from_query(query)?;
+        // Fail early on unsupported representations, before touching the ledger
+        let content_type = params.content_type(&headers)?;
+        did_url.apply_did_parameters(&mut params)?;
+        Ok::<_, DidSovDriverError>((params, content_type, did_url))
+    })?;
 
-    Ok(response)
+    let (response, max_age) =
+        resolve_did_with_cache(did_url.did.clone(), &params, &networks, &state).await?;
+
+    let _span = tracing::info_span!("build_response").entered();
+    if did_url.selects_resource() || content_type == ContentType::DidUrlDereferencing {
+        Ok(dereference(&did_url, response, &params, content_type)?.into_response())
+    } else {
+        let last_modified = response.last_updated();
+        let validators = CacheValidators::new(&response, content_type, last_modified, max_age);
+        if validators.is_not_modified(&headers) {
+            return Ok(validators.not_modified());
+        }
+        let representation = finalize_response(response, &params, content_type);
+        let mut res = representation.into_response();
+        validators.apply(res.headers_mut());
+        Ok(res)
+    }
 }
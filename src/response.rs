@@ -19,17 +19,116 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::Value;
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
 
+use crate::representation::ContentType;
+
+const DID_CONTEXT: &str = "https://www.w3.org/ns/did/v1";
+/// Key of the resolution metadata specific to this driver.
+pub const DRIVER_METADATA_KEY: &str = "driverDidSov";
+
+/// DID Resolution Result as produced by the resolver, kept in this form in the cache so that
+/// any representation can be rendered from it.
 #[derive(Debug, Clone)]
 pub struct DIDJsonResponse(pub Value);
 
-impl IntoResponse for DIDJsonResponse {
+impl DIDJsonResponse {
+    pub fn insert_resolution_metadata(&mut self, key: &str, value: Value) {
+        if let Some(metadata) = self
+            .0
+            .get_mut("didResolutionMetadata")
+            .and_then(Value::as_object_mut)
+        {
+            metadata.insert(key.to_string(), value);
+        }
+    }
+
+    pub fn into_representation(self, content_type: ContentType) -> DIDRepresentation {
+        let body = match content_type {
+            // Dereferencing results are rendered by crate::dereference
+            ContentType::DidResolution | ContentType::DidUrlDereferencing => {
+                let mut response = self;
+                response
+                    .insert_resolution_metadata("contentType", json!(content_type.media_type()));
+                response.0
+            }
+            ContentType::DidLdJson => {
+                let mut document = self.did_document().clone();
+                if let Some(document) = document.as_object_mut() {
+                    document
+                        .entry("@context")
+                        .or_insert_with(|| json!(DID_CONTEXT));
+                }
+                document
+            }
+            ContentType::DidJson => self.did_document().clone(),
+        };
+        DIDRepresentation { content_type, body }
+    }
+
+    /// Replaces `from` with `to` wherever it appears as a DID or the DID of a DID URL.
+    pub fn replace_did(&mut self, from: &str, to: &str) {
+        replace_did_in_value(&mut self.0, from, to);
+    }
+
+    pub fn did_document(&self) -> &Value {
+        &self.0["didDocument"]
+    }
+
+    pub fn did_document_metadata(&self) -> &Value {
+        &self.0["didDocumentMetadata"]
+    }
+
+    /// When the DID document was last updated on the ledger, according to its metadata.
+    pub fn last_updated(&self) -> Option<DateTime<Utc>> {
+        let metadata = self.did_document_metadata();
+        metadata["updated"]
+            .as_str()
+            .or_else(|| metadata["created"].as_str())
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+    }
+}
+
+fn replace_did_in_value(value: &mut Value, from: &str, to: &str) {
+    match value {
+        Value::String(string) => {
+            if let Some(rest) = string.strip_prefix(from) {
+                if rest.is_empty() || rest.starts_with(['#', '?', '/', ';']) {
+                    *string = format!("{}{}", to, rest);
+                }
+            }
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| replace_did_in_value(value, from, to)),
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|value| replace_did_in_value(value, from, to)),
+        _ => {}
+    }
+}
+
+/// A resolution result rendered in the representation negotiated with the client.
+#[derive(Debug)]
+pub struct DIDRepresentation {
+    content_type: ContentType,
+    body: Value,
+}
+
+impl DIDRepresentation {
+    pub fn body(&self) -> &Value {
+        &self.body
+    }
+}
+
+impl IntoResponse for DIDRepresentation {
     fn into_response(self) -> Response {
-        let mut res = Json(self.0).into_response();
+        let mut res = Json(self.body).into_response();
         res.headers_mut().insert(
             axum::http::header::CONTENT_TYPE,
-            HeaderValue::from_static("application/did+json"),
+            HeaderValue::from_static(self.content_type.media_type()),
         );
         res
     }
@@ -0,0 +1,70 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::future::Future;
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::config::RetryConfig;
+use crate::error::DidSovDriverError;
+
+fn is_retryable(config: &RetryConfig, err: &DidSovDriverError) -> bool {
+    let kind = err.kind().0;
+    config.retryable.iter().any(|retryable| retryable == kind)
+}
+
+/// Backoff before the given retry, drawn uniformly up to an exponentially growing bound.
+fn backoff(config: &RetryConfig, retry: u32) -> Duration {
+    let bound = config
+        .initial_backoff_ms
+        .saturating_mul(2u64.saturating_pow(retry))
+        .min(config.max_backoff_ms);
+    Duration::from_millis(rand::thread_rng().gen_range(0..=bound))
+}
+
+/// Runs `operation` until it succeeds, fails with an error which is not retryable, or runs out of
+/// attempts. Returns the outcome of the last attempt along with the number of retries.
+pub async fn with_retries<T, F, Fut>(
+    config: &RetryConfig,
+    description: &str,
+    mut operation: F,
+) -> (Result<T, DidSovDriverError>, u32)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, DidSovDriverError>>,
+{
+    let max_attempts = config.max_attempts.max(1);
+    let mut retries = 0;
+    loop {
+        match operation().await {
+            Err(err) if retries + 1 < max_attempts && is_retryable(config, &err) => {
+                let backoff = backoff(config, retries);
+                retries += 1;
+                warn!(
+                    "{} failed, retry {} of {} in {} ms: {}",
+                    description,
+                    retries,
+                    max_attempts - 1,
+                    backoff.as_millis(),
+                    err
+                );
+                tokio::time::sleep(backoff).await;
+            }
+            result => return (result, retries),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod utils;
+
+use aries_vcx::utils::devsetup::SetupProfile;
+use hyper::{Method, StatusCode};
+use serde_json::Value;
+use utils::{
+    body_to_json, purge_did, send_admin_request, send_request, write_endpoint_url,
+    write_test_endpoint, ADMIN_TOKEN, DRIVER_PORT,
+};
+
+async fn service_endpoint(did: &str) -> Value {
+    let response = send_request(did).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    body_to_json(response).await["didDocument"]["service"][0]["serviceEndpoint"].clone()
+}
+
+#[tokio::test]
+async fn test_admin_requires_token() {
+    let response = send_admin_request(Method::GET, "/stats", None)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send_admin_request(Method::DELETE, "", Some("wrong-token"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_admin_cache_stats() {
+    let response = send_admin_request(Method::GET, "/stats", Some(ADMIN_TOKEN))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body_json = body_to_json(response).await;
+    for field in ["size", "capacity", "hits", "misses", "evictions"] {
+        assert!(body_json[field].is_u64());
+    }
+}
+
+#[tokio::test]
+async fn test_admin_purge_and_refresh_did() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+        purge_did(DRIVER_PORT, &did).await;
+        assert_eq!(service_endpoint(&did).await, "http://localhost:8080/");
+
+        write_endpoint_url(&init, "http://localhost:8081").await;
+        assert_eq!(service_endpoint(&did).await, "http://localhost:8080/");
+        purge_did(DRIVER_PORT, &did).await;
+        assert_eq!(service_endpoint(&did).await, "http://localhost:8081/");
+
+        write_endpoint_url(&init, "http://localhost:8082").await;
+        assert_eq!(service_endpoint(&did).await, "http://localhost:8081/");
+        let response = send_admin_request(
+            Method::POST,
+            &format!("/{}/refresh", did),
+            Some(ADMIN_TOKEN),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(service_endpoint(&did).await, "http://localhost:8082/");
+
+        write_test_endpoint(&init).await;
+        purge_did(DRIVER_PORT, &did).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_admin_purge_did_written_with_namespace() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        let namespaced_did = format!("did:sov:localhost:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+        purge_did(DRIVER_PORT, &did).await;
+        assert_eq!(
+            service_endpoint(&namespaced_did).await,
+            "http://localhost:8080/"
+        );
+
+        // Both ways of writing the DID share the cached result
+        write_endpoint_url(&init, "http://localhost:8081").await;
+        let response = send_request(&did).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body_json = body_to_json(response).await;
+        assert_eq!(body_json["didDocument"]["id"], did.as_str());
+        assert_eq!(
+            body_json["didDocument"]["service"][0]["serviceEndpoint"],
+            "http://localhost:8080/"
+        );
+
+        purge_did(DRIVER_PORT, &namespaced_did).await;
+        assert_eq!(service_endpoint(&did).await, "http://localhost:8081/");
+
+        write_test_endpoint(&init).await;
+        purge_did(DRIVER_PORT, &did).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_admin_refresh_non_existent_did() {
+    let response = send_admin_request(
+        Method::POST,
+        "/did:sov:Ui4ZyjJrnhvsnW2qLCBCUq/refresh",
+        Some(ADMIN_TOKEN),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
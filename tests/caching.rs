@@ -0,0 +1,192 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod utils;
+
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use aries_vcx::utils::devsetup::SetupProfile;
+use hyper::header::CACHE_CONTROL;
+use hyper::StatusCode;
+use serde_json::Value;
+use utils::{
+    body_to_json, cache_stats, purge_did, send_get_request_to, send_request_to, write_endpoint_url,
+    write_test_endpoint,
+};
+
+/// Ports of two replicas of the driver sharing their cache in redis. The second one prewarms the
+/// DID of the tests, so that its resolutions are pinned.
+const REDIS_DRIVER_PORTS: [u16; 2] = [4001, 4002];
+
+/// Port of the driver which prewarms the DID of the tests every second.
+const PREWARM_DRIVER_PORT: u16 = 4005;
+/// DID of the tests, as configured to be prewarmed in ci/docker-compose.yml.
+const PREWARMED_DID: &str = "did:sov:V4SGRU86Z58d6TV7PBUe6f";
+
+/// Port of the driver which serves stale entries while revalidating them, with a 2 s TTL.
+const SWR_DRIVER_PORT: u16 = 4003;
+
+/// Port of the driver with the disk cache backend, with a 30 s TTL.
+const DISK_DRIVER_PORT: u16 = 4004;
+const DISK_DRIVER_CONTAINER: &str = "driver-did-sov-disk";
+
+/// Restarts the container of a driver and waits until it is ready to serve requests again.
+async fn restart_driver(container: &str, port: u16) {
+    let status = Command::new("docker")
+        .args(["restart", container])
+        .status()
+        .unwrap();
+    assert!(status.success(), "Failed to restart {}", container);
+    let started = Instant::now();
+    loop {
+        if let Ok(response) = send_get_request_to(port, "/health/ready").await {
+            if response.status() == StatusCode::OK {
+                return;
+            }
+        }
+        assert!(
+            started.elapsed() < Duration::from_secs(20),
+            "{} did not restart",
+            container
+        );
+        thread::sleep(Duration::from_millis(250));
+    }
+}
+
+async fn resolve_endpoint(port: u16, did: &str) -> (Value, String) {
+    let response = send_request_to(port, did).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let cache_control = response.headers()[CACHE_CONTROL]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body_json = body_to_json(response).await;
+    (
+        body_json["didDocument"]["service"][0]["serviceEndpoint"].clone(),
+        cache_control,
+    )
+}
+
+#[tokio::test]
+async fn test_stale_while_revalidate() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+        purge_did(SWR_DRIVER_PORT, &did).await;
+        let (endpoint, cache_control) = resolve_endpoint(SWR_DRIVER_PORT, &did).await;
+        assert_eq!(endpoint, "http://localhost:8080/");
+        assert_eq!(cache_control, "max-age=2");
+
+        write_endpoint_url(&init, "http://localhost:8081").await;
+        thread::sleep(Duration::from_secs(3));
+        let (endpoint, cache_control) = resolve_endpoint(SWR_DRIVER_PORT, &did).await;
+        assert_eq!(endpoint, "http://localhost:8080/");
+        assert_eq!(cache_control, "max-age=0");
+
+        thread::sleep(Duration::from_secs(1));
+        let (endpoint, cache_control) = resolve_endpoint(SWR_DRIVER_PORT, &did).await;
+        assert_eq!(endpoint, "http://localhost:8081/");
+        assert_ne!(cache_control, "max-age=0");
+
+        write_test_endpoint(&init).await;
+        purge_did(SWR_DRIVER_PORT, &did).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_disk_cache_survives_restart() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+        purge_did(DISK_DRIVER_PORT, &did).await;
+        let (endpoint, _) = resolve_endpoint(DISK_DRIVER_PORT, &did).await;
+        assert_eq!(endpoint, "http://localhost:8080/");
+        let cached_at = Instant::now();
+
+        write_endpoint_url(&init, "http://localhost:8081").await;
+        restart_driver(DISK_DRIVER_CONTAINER, DISK_DRIVER_PORT).await;
+        let (endpoint, cache_control) = resolve_endpoint(DISK_DRIVER_PORT, &did).await;
+        assert_eq!(endpoint, "http://localhost:8080/");
+        // The TTL runs from the insertion before the restart
+        let max_age = cache_control
+            .strip_prefix("max-age=")
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+        assert!(max_age as f64 <= 30.0 - cached_at.elapsed().as_secs_f64());
+
+        thread::sleep(Duration::from_secs(31).saturating_sub(cached_at.elapsed()));
+        let (endpoint, _) = resolve_endpoint(DISK_DRIVER_PORT, &did).await;
+        assert_eq!(endpoint, "http://localhost:8081/");
+
+        write_test_endpoint(&init).await;
+        purge_did(DISK_DRIVER_PORT, &did).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_redis_purge_reaches_replicas() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        let [replica_1, replica_2] = REDIS_DRIVER_PORTS;
+        write_test_endpoint(&init).await;
+        purge_did(replica_1, &did).await;
+        for port in REDIS_DRIVER_PORTS {
+            let (endpoint, _) = resolve_endpoint(port, &did).await;
+            assert_eq!(endpoint, "http://localhost:8080/");
+        }
+
+        write_endpoint_url(&init, "http://localhost:8081").await;
+        let (endpoint, _) = resolve_endpoint(replica_2, &did).await;
+        assert_eq!(endpoint, "http://localhost:8080/");
+
+        purge_did(replica_1, &did).await;
+        // Leave time for the purge to be broadcast
+        thread::sleep(Duration::from_millis(200));
+        let (endpoint, _) = resolve_endpoint(replica_2, &did).await;
+        assert_eq!(endpoint, "http://localhost:8081/");
+
+        write_test_endpoint(&init).await;
+        purge_did(replica_1, &did).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_prewarmed_did_is_cached() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        assert_eq!(did, PREWARMED_DID);
+        write_test_endpoint(&init).await;
+        // Leave time for a refresh, should the DID have been purged
+        thread::sleep(Duration::from_millis(1500));
+
+        let stats_before = cache_stats(PREWARM_DRIVER_PORT).await;
+        let (endpoint, _) = resolve_endpoint(PREWARM_DRIVER_PORT, &did).await;
+        assert_eq!(endpoint, "http://localhost:8080/");
+        let stats_after = cache_stats(PREWARM_DRIVER_PORT).await;
+        assert_eq!(
+            stats_after["hits"].as_u64().unwrap(),
+            stats_before["hits"].as_u64().unwrap() + 1
+        );
+        assert_eq!(stats_after["misses"], stats_before["misses"]);
+    })
+    .await;
+}
@@ -0,0 +1,97 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod utils;
+
+use aries_vcx::utils::devsetup::SetupProfile;
+use serde_json::Value;
+use utils::{
+    body_to_json, send_request, send_request_with_accept, send_request_with_query,
+    write_test_endpoint,
+};
+
+async fn resolve_document(did: &str) -> Value {
+    let response = send_request(did).await.unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    body_to_json(response).await["didDocument"].clone()
+}
+
+fn fragment_of(id: &str) -> &str {
+    id.split_once('#').unwrap().1
+}
+
+#[tokio::test]
+async fn test_dereference_fragment() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        let document = resolve_document(&did).await;
+        let verification_method = &document["verificationMethod"][0];
+        let fragment = fragment_of(verification_method["id"].as_str().unwrap());
+
+        let did_url = format!("{}%23{}", did, fragment);
+        let response = send_request(&did_url).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        let body_json = body_to_json(response).await;
+        assert_eq!(&body_json, verification_method);
+
+        let accept = "application/ld+json;profile=\"https://w3id.org/did-url-dereferencing\"";
+        let response = send_request_with_accept(&did_url, accept).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        let body_json = body_to_json(response).await;
+        assert_eq!(&body_json["contentStream"], verification_method);
+        assert_eq!(
+            body_json["dereferencingMetadata"]["contentType"],
+            "application/did+json"
+        );
+
+        let did_url = format!("{}%23unknown-fragment", did);
+        let response = send_request(&did_url).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_dereference_service_relative_ref() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+
+        let document = resolve_document(&did).await;
+        let service = fragment_of(document["service"][0]["id"].as_str().unwrap());
+
+        let did_url = format!(
+            "{}%3Fservice%3D{}%26relativeRef%3D%252Fpath%252Fto%252Fresource",
+            did, service
+        );
+        let response = send_request(&did_url).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::SEE_OTHER);
+        let location = response.headers().get("location").unwrap();
+        assert_eq!(location, "http://localhost:8080/path/to/resource");
+
+        let query = format!("service={}&relativeRef=%2Fpath%2Fto%2Fresource", service);
+        let response = send_request_with_query(&did, &query).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::SEE_OTHER);
+        let location = response.headers().get("location").unwrap();
+        assert_eq!(location, "http://localhost:8080/path/to/resource");
+
+        let did_url = format!("{}%3Fservice%3D{}", did, service);
+        let query = format!("service={}", service);
+        let response = send_request_with_query(&did_url, &query).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+    })
+    .await;
+}
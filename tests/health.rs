@@ -0,0 +1,37 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod utils;
+
+use serde_json::Value;
+use utils::send_get_request;
+
+#[tokio::test]
+async fn test_liveness() {
+    let response = send_get_request("/health/live").await.unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_readiness() {
+    let response = send_get_request("/health/ready").await.unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+
+    let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body_json: Value = serde_json::from_slice(&body_bytes).unwrap();
+    assert_eq!(body_json["status"], "up");
+    assert_eq!(body_json["networks"]["localhost"]["status"], "up");
+}
@@ -0,0 +1,45 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod utils;
+
+use utils::{send_get_request, send_request};
+
+#[tokio::test]
+async fn test_metrics() {
+    let response = send_request("did:sov:KxDPhdCQ2YhKuVzKnAJiSU")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+
+    let response = send_get_request("/metrics").await.unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body = String::from_utf8(body_bytes.to_vec()).unwrap();
+
+    assert!(
+        body.contains("driver_did_sov_http_requests_total{error=\"resolve_error\",status=\"404\"}")
+    );
+    assert!(!body.contains("error=\"cached\""));
+    for metric in [
+        "driver_did_sov_http_requests_in_flight",
+        "driver_did_sov_resolution_duration_seconds_bucket",
+        "driver_did_sov_cache_entries",
+        "driver_did_sov_cache_evictions_total",
+    ] {
+        assert!(body.contains(metric), "{} is missing", metric);
+    }
+}
@@ -16,7 +16,11 @@
 
 mod utils;
 
-use utils::send_request;
+use hyper::header::{CONTENT_TYPE, RETRY_AFTER};
+use hyper::{Body, Client, Request};
+use utils::{
+    cache_stats, send_request, send_request_with_accept, send_request_with_query, DRIVER_PORT,
+};
 
 #[tokio::test]
 async fn test_resolve_non_existent_did() {
@@ -25,6 +29,37 @@ async fn test_resolve_non_existent_did() {
     let response = send_request(non_existent_did).await.unwrap();
 
     assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.headers().get(CONTENT_TYPE).unwrap(),
+        "application/ld+json;profile=\"https://w3id.org/did-resolution\""
+    );
+    let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body_json: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+    assert!(body_json["didDocument"].is_null());
+    assert_eq!(body_json["didResolutionMetadata"]["error"], "notFound");
+    assert!(body_json["didResolutionMetadata"]["errorMessage"].is_string());
+}
+
+#[tokio::test]
+async fn test_resolve_non_existent_did_repeatedly() {
+    let non_existent_did = "did:sov:Bv5D9PKnEWKmX5pxrX7PYZ";
+
+    let mut bodies = Vec::new();
+    let mut stats = Vec::new();
+    for _ in 0..2 {
+        let response = send_request(non_existent_did).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+        let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        bodies.push(body_bytes);
+        stats.push(cache_stats(DRIVER_PORT).await);
+    }
+
+    assert_eq!(bodies[0], bodies[1]);
+    assert_eq!(
+        stats[1]["hits"].as_u64().unwrap(),
+        stats[0]["hits"].as_u64().unwrap() + 1
+    );
+    assert_eq!(stats[1]["misses"], stats[0]["misses"]);
 }
 
 #[tokio::test]
@@ -44,3 +79,94 @@ async fn test_resolve_unsupported_did_method() {
 
     assert_eq!(response.status(), hyper::StatusCode::NOT_IMPLEMENTED);
 }
+
+#[tokio::test]
+async fn test_resolve_unsupported_representation() {
+    let did = "did:sov:KxDPhdCQ2YhKuVzKnAJiSU";
+
+    let response = send_request_with_accept(did, "application/xml")
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), hyper::StatusCode::NOT_ACCEPTABLE);
+    let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body_json: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+    assert_eq!(
+        body_json["didResolutionMetadata"]["error"],
+        "representationNotSupported"
+    );
+}
+
+#[tokio::test]
+async fn test_resolve_invalid_version_options() {
+    let did = "did:sov:KxDPhdCQ2YhKuVzKnAJiSU";
+
+    for query in [
+        "versionId=latest",
+        "versionTime=yesterday",
+        "versionId=1&versionTime=2023-01-01T00:00:00Z",
+    ] {
+        let response = send_request_with_query(did, query).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn test_resolve_invalid_timeout_option() {
+    let did = "did:sov:KxDPhdCQ2YhKuVzKnAJiSU";
+
+    for query in ["timeout=0", "timeout=soon"] {
+        let response = send_request_with_query(did, query).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+    }
+}
+
+#[tokio::test]
+async fn test_resolve_exceeding_timeout() {
+    let did = "did:sov:9wvq2i4xUa5umXoThe83CT";
+
+    let response = send_request_with_query(did, "noCache=true&timeout=1")
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), hyper::StatusCode::GATEWAY_TIMEOUT);
+    assert!(response.headers().contains_key(RETRY_AFTER));
+    let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let body_json: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+    assert_eq!(body_json["didResolutionMetadata"]["error"], "ledgerTimeout");
+}
+
+#[tokio::test]
+async fn test_resolve_unknown_network_namespace() {
+    let unknown_network_did = "did:sov:unknown:KxDPhdCQ2YhKuVzKnAJiSU";
+
+    let response = send_request(unknown_network_did).await.unwrap();
+
+    assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_batch_invalid_body() {
+    for (content_type, body) in [
+        (
+            "application/json",
+            "{\"did\": \"did:sov:KxDPhdCQ2YhKuVzKnAJiSU\"}",
+        ),
+        ("application/json", "[\"did:sov:KxDPhdCQ2YhKuVzKnAJiSU\""),
+        ("text/plain", "[\"did:sov:KxDPhdCQ2YhKuVzKnAJiSU\"]"),
+    ] {
+        let request = Request::post("http://localhost:4000/1.0/identifiers")
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap();
+        let response = Client::new().request(request).await.unwrap();
+
+        assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+        let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body_json: serde_json::Value = serde_json::from_slice(&body_bytes).unwrap();
+        assert_eq!(
+            body_json["didResolutionMetadata"]["error"],
+            "invalidRequest"
+        );
+    }
+}
@@ -16,29 +16,22 @@
 
 mod utils;
 
-use std::thread;
 use std::time::Duration;
 
-use aries_vcx::common::ledger::{
-    service_didsov::{DidSovServiceType, EndpointDidSov},
-    transactions::write_endpoint,
-};
 use aries_vcx::utils::devsetup::SetupProfile;
 
-use utils::send_request;
+use utils::{
+    metric_total, purge_did, send_request, send_request_with_query, write_test_endpoint,
+    DRIVER_PORT,
+};
+
+const LEDGER_READS: &str = "driver_did_sov_ledger_read_duration_seconds_count";
 
 #[tokio::test]
 async fn test_resolver_performance() {
     SetupProfile::run(|init| async move {
         let did = format!("did:sov:{}", init.institution_did);
-        let endpoint = EndpointDidSov::create()
-            .set_service_endpoint("http://localhost:8080".parse().unwrap())
-            .set_routing_keys(Some(vec!["key1".to_string(), "key2".to_string()]))
-            .set_types(Some(vec![DidSovServiceType::Endpoint]));
-        write_endpoint(&init.profile, &init.institution_did, &endpoint)
-            .await
-            .unwrap();
-        thread::sleep(Duration::from_millis(50));
+        write_test_endpoint(&init).await;
 
         let start = std::time::Instant::now();
         let num_requests = 100;
@@ -52,3 +45,85 @@ async fn test_resolver_performance() {
     })
     .await;
 }
+
+#[tokio::test]
+async fn test_resolver_throughput_under_concurrent_load() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+        // Warm up the cache so that the measurement covers cache reads only
+        send_request(&did).await.unwrap();
+
+        let start = std::time::Instant::now();
+        let (num_tasks, requests_per_task) = (20, 50);
+        let handles = (0..num_tasks)
+            .map(|_| {
+                let did = did.clone();
+                tokio::spawn(async move {
+                    for _ in 0..requests_per_task {
+                        let response = send_request(&did).await.unwrap();
+                        assert_eq!(response.status(), hyper::StatusCode::OK);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        let elapsed = start.elapsed();
+        let num_requests = num_tasks * requests_per_task;
+        println!(
+            "{num_requests} requests from {num_tasks} concurrent clients took: {elapsed:?} ({:.0} requests/s)",
+            num_requests as f64 / elapsed.as_secs_f64()
+        );
+        assert!(elapsed < Duration::from_secs(10));
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_concurrent_resolutions_of_same_did() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+        purge_did(DRIVER_PORT, &did).await;
+        let reads_before = metric_total(LEDGER_READS).await;
+
+        let handles = (0..50)
+            .map(|_| {
+                let did = did.clone();
+                tokio::spawn(async move {
+                    let response = send_request(&did).await.unwrap();
+                    assert_eq!(response.status(), hyper::StatusCode::OK);
+                    hyper::body::to_bytes(response.into_body()).await.unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut bodies = Vec::new();
+        for handle in handles {
+            bodies.push(handle.await.unwrap());
+        }
+        assert!(bodies.windows(2).all(|pair| pair[0] == pair[1]));
+        assert_eq!(metric_total(LEDGER_READS).await - reads_before, 1.0);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_read_outlives_timed_out_resolution() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+        purge_did(DRIVER_PORT, &did).await;
+        let reads_before = metric_total(LEDGER_READS).await;
+
+        let response = send_request_with_query(&did, "timeout=1").await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::GATEWAY_TIMEOUT);
+        // Shares the read started by the timed out resolution, or finds its result cached
+        let response = send_request(&did).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        assert_eq!(metric_total(LEDGER_READS).await - reads_before, 1.0);
+    })
+    .await;
+}
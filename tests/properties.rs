@@ -0,0 +1,47 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod utils;
+
+use serde_json::{json, Value};
+use utils::send_get_request;
+
+async fn get_json(path: &str) -> Value {
+    let response = send_get_request(path).await.unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    serde_json::from_slice(&body_bytes).unwrap()
+}
+
+#[tokio::test]
+async fn test_methods() {
+    assert_eq!(get_json("/1.0/methods").await, json!(["sov"]));
+}
+
+#[tokio::test]
+async fn test_properties() {
+    let properties = get_json("/1.0/properties").await;
+
+    assert_eq!(properties["methods"], json!(["sov"]));
+    assert_eq!(properties["defaultNetwork"], "localhost");
+    assert_eq!(properties["networks"]["localhost"]["name"], "localhost");
+    assert!(properties["contentTypes"]
+        .as_array()
+        .unwrap()
+        .contains(&json!("application/did+json")));
+    assert!(properties["cache"]["ttlSecs"].is_u64());
+    assert!(properties["version"].is_string());
+}
@@ -0,0 +1,51 @@
+/*
+ * Copyright 2023 ABSA Group Limited
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+mod utils;
+
+use hyper::header::HeaderName;
+use utils::{send_request, send_request_with_header};
+
+const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+#[tokio::test]
+async fn test_request_id_echoed() {
+    let response = send_request_with_header(
+        "did:sov:KxDPhdCQ2YhKuVzKnAJiSU",
+        REQUEST_ID_HEADER,
+        "test-request-id",
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        response.headers().get(REQUEST_ID_HEADER).unwrap(),
+        "test-request-id"
+    );
+}
+
+#[tokio::test]
+async fn test_request_id_generated() {
+    let first = send_request("did:sov:KxDPhdCQ2YhKuVzKnAJiSU")
+        .await
+        .unwrap();
+    let second = send_request("did:sov:KxDPhdCQ2YhKuVzKnAJiSU")
+        .await
+        .unwrap();
+    let first_id = first.headers().get(REQUEST_ID_HEADER).unwrap();
+    let second_id = second.headers().get(REQUEST_ID_HEADER).unwrap();
+    assert!(!first_id.is_empty());
+    assert_ne!(first_id, second_id);
+}
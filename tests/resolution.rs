@@ -19,34 +19,30 @@ mod utils;
 use std::thread;
 use std::time::Duration;
 
-use aries_vcx::common::ledger::{
-    service_didsov::{DidSovServiceType, EndpointDidSov},
-    transactions::write_endpoint,
-};
+use aries_vcx::common::keys::rotate_verkey;
+use aries_vcx::common::ledger::transactions::add_new_did;
 use aries_vcx::utils::devsetup::SetupProfile;
 use serde_json::Value;
-use utils::send_request;
+use utils::{
+    body_to_json, purge_did, send_batch_request, send_request, send_request_with_accept,
+    send_request_with_header, send_request_with_query, write_endpoint_url, write_test_endpoint,
+    DRIVER_PORT,
+};
 
 #[tokio::test]
 async fn test_resolve_did() {
     SetupProfile::run(|init| async move {
         let did = format!("did:sov:{}", init.institution_did);
-        let endpoint = EndpointDidSov::create()
-            .set_service_endpoint("http://localhost:8080".parse().unwrap())
-            .set_routing_keys(Some(vec!["key1".to_string(), "key2".to_string()]))
-            .set_types(Some(vec![DidSovServiceType::Endpoint]));
-        write_endpoint(&init.profile, &init.institution_did, &endpoint)
-            .await
-            .unwrap();
-        thread::sleep(Duration::from_millis(50));
+        write_test_endpoint(&init).await;
         let response = send_request(&did).await.unwrap();
         assert_eq!(response.status(), hyper::StatusCode::OK);
         let content_type = response.headers().get("content-type").unwrap();
-        assert_eq!(content_type, "application/did+json");
+        assert_eq!(
+            content_type,
+            "application/ld+json;profile=\"https://w3id.org/did-resolution\""
+        );
 
-        let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        let body_str = String::from_utf8(body_bytes.to_vec()).unwrap();
-        let body_json: Value = serde_json::from_str(&body_str).unwrap();
+        let body_json = body_to_json(response).await;
 
         assert!(body_json.is_object());
         let did_document = body_json.get("didDocument").unwrap().as_object().unwrap();
@@ -66,3 +62,239 @@ async fn test_resolve_did() {
     })
     .await;
 }
+
+#[tokio::test]
+async fn test_resolve_did_document_only() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+
+        for accept in ["application/did+json", "application/did+ld+json"] {
+            let response = send_request_with_accept(&did, accept).await.unwrap();
+            assert_eq!(response.status(), hyper::StatusCode::OK);
+            let content_type = response.headers().get("content-type").unwrap();
+            assert_eq!(content_type, accept);
+
+            let body_json = body_to_json(response).await;
+            assert!(body_json.get("didDocument").is_none());
+            assert!(body_json.get("didResolutionMetadata").is_none());
+            let id = body_json.get("id").unwrap().as_str().unwrap();
+            assert_eq!(id, did);
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_resolve_did_resolution_result() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+        purge_did(DRIVER_PORT, &did).await;
+
+        let accept = "application/ld+json;profile=\"https://w3id.org/did-resolution\"";
+        let response = send_request_with_accept(&did, accept).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+
+        let body_json = body_to_json(response).await;
+        let metadata = body_json.get("didResolutionMetadata").unwrap();
+        let content_type = metadata.get("contentType").unwrap().as_str().unwrap();
+        assert_eq!(content_type, accept);
+        // Reported only when the read from the ledger was retried
+        if let Some(driver_metadata) = metadata.get("driverDidSov") {
+            assert!(driver_metadata["retries"].as_u64().unwrap() > 0);
+        }
+        let id = body_json["didDocument"]["id"].as_str().unwrap();
+        assert_eq!(id, did);
+
+        // Retries are reported only by the request which read the ledger
+        let response = send_request_with_accept(&did, accept).await.unwrap();
+        let cached_body_json = body_to_json(response).await;
+        assert!(cached_body_json["didResolutionMetadata"]["driverDidSov"].is_null());
+        if metadata.get("driverDidSov").is_none() {
+            assert_eq!(cached_body_json, body_json);
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_resolve_did_conditional_request() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+        purge_did(DRIVER_PORT, &did).await;
+
+        let response = send_request(&did).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        let headers = response.headers();
+        let etag = headers.get("etag").unwrap().to_str().unwrap().to_string();
+        let cache_control = headers.get("cache-control").unwrap().to_str().unwrap();
+        assert!(cache_control.starts_with("max-age="));
+
+        let response = send_request_with_header(&did, hyper::header::IF_NONE_MATCH, &etag)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get("etag").unwrap(), etag.as_str());
+
+        // Unknown options only change the resolution metadata
+        let response = send_request_with_query(&did, "foo=bar").await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        assert_eq!(response.headers().get("etag").unwrap(), etag.as_str());
+
+        let response = send_request_with_header(&did, hyper::header::IF_NONE_MATCH, "\"other\"")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_resolve_did_with_options() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+
+        let query = "accept=application%2Fdid%2Bjson&noCache=true";
+        let response = send_request_with_query(&did, query).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        let content_type = response.headers().get("content-type").unwrap();
+        assert_eq!(content_type, "application/did+json");
+
+        let response = send_request_with_query(&did, "foo=bar").await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        let body_json = body_to_json(response).await;
+        let unknown_options = body_json["didResolutionMetadata"]["unknownOptions"]
+            .as_array()
+            .unwrap();
+        assert_eq!(unknown_options, &vec![Value::from("foo")]);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_resolve_did_version() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_endpoint_url(&init, "http://localhost:8080").await;
+        // Ledger transaction times have a resolution of one second
+        thread::sleep(Duration::from_secs(2));
+        let version_time = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        thread::sleep(Duration::from_secs(2));
+        write_endpoint_url(&init, "http://localhost:8081").await;
+
+        let query = format!("versionTime={}", version_time);
+        let response = send_request_with_query(&did, &query).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        let body_json = body_to_json(response).await;
+        let service_endpoint = body_json["didDocument"]["service"][0]["serviceEndpoint"]
+            .as_str()
+            .unwrap();
+        assert_eq!(service_endpoint, "http://localhost:8080/");
+        let metadata = body_json.get("didDocumentMetadata").unwrap();
+        assert!(metadata.get("nextUpdate").is_some());
+        let version_id = metadata["versionId"].as_str().unwrap();
+
+        let query = format!("versionId={}", version_id);
+        let response = send_request_with_query(&did, &query).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        let body_json = body_to_json(response).await;
+        assert_eq!(body_json["didDocumentMetadata"]["versionId"], version_id);
+        let service_endpoint = body_json["didDocument"]["service"][0]["serviceEndpoint"]
+            .as_str()
+            .unwrap();
+        assert_eq!(service_endpoint, "http://localhost:8080/");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_resolve_did_version_after_key_rotation() {
+    SetupProfile::run(|init| async move {
+        // A DID of its own, as rotating the key of the institution DID would affect other tests
+        let (did_id, _) = add_new_did(&init.profile, &init.institution_did, None).await;
+        let did = format!("did:sov:{}", did_id);
+        let resolve_version = |version_time: String| {
+            let did = did.clone();
+            async move {
+                let query = format!("versionTime={}", version_time);
+                let response = send_request_with_query(&did, &query).await.unwrap();
+                assert_eq!(response.status(), hyper::StatusCode::OK);
+                body_to_json(response).await
+            }
+        };
+        let now = || chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+
+        // Ledger transaction times have a resolution of one second
+        thread::sleep(Duration::from_secs(2));
+        let before_rotation = resolve_version(now()).await;
+        rotate_verkey(&init.profile, &did_id).await.unwrap();
+        thread::sleep(Duration::from_secs(2));
+        let after_rotation = resolve_version(now()).await;
+
+        assert_ne!(
+            before_rotation["didDocument"]["verificationMethod"][0]["publicKeyBase58"],
+            after_rotation["didDocument"]["verificationMethod"][0]["publicKeyBase58"]
+        );
+        assert_eq!(
+            before_rotation["didDocumentMetadata"]["created"],
+            after_rotation["didDocumentMetadata"]["created"]
+        );
+        assert_ne!(
+            after_rotation["didDocumentMetadata"]["created"],
+            after_rotation["didDocumentMetadata"]["updated"]
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_resolve_did_with_network_namespace() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:localhost:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+
+        let response = send_request(&did).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        let body_json = body_to_json(response).await;
+        let id = body_json["didDocument"]["id"].as_str().unwrap();
+        assert_eq!(id, did);
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_resolve_did_batch() {
+    SetupProfile::run(|init| async move {
+        let did = format!("did:sov:{}", init.institution_did);
+        write_test_endpoint(&init).await;
+        let non_existent_did = "did:sov:KxDPhdCQ2YhKuVzKnAJiSU";
+        let malformed_did = "did:sov:malformedDID!";
+
+        let response = send_batch_request(&[&did, non_existent_did, malformed_did])
+            .await
+            .unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+
+        let body_json = body_to_json(response).await;
+        assert_eq!(body_json.as_object().unwrap().len(), 3);
+        assert_eq!(body_json[&did]["status"], 200);
+        let id = body_json[&did]["didResolutionResult"]["didDocument"]["id"]
+            .as_str()
+            .unwrap();
+        assert_eq!(id, did);
+        assert_eq!(body_json[non_existent_did]["status"], 404);
+        assert_eq!(
+            body_json[non_existent_did]["didResolutionResult"]["didResolutionMetadata"]["error"],
+            "notFound"
+        );
+        assert_eq!(body_json[malformed_did]["status"], 400);
+        assert_eq!(
+            body_json[malformed_did]["didResolutionResult"]["didResolutionMetadata"]["error"],
+            "invalidDid"
+        );
+    })
+    .await;
+}
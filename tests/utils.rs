@@ -14,10 +14,151 @@
  * limitations under the License.
  */
 
-use hyper::{client::ResponseFuture, Client, Uri};
+#![allow(dead_code)]
+
+use aries_vcx::common::ledger::{
+    service_didsov::{DidSovServiceType, EndpointDidSov},
+    transactions::write_endpoint,
+};
+use aries_vcx::utils::devsetup::SetupProfile;
+use hyper::{
+    client::ResponseFuture,
+    header::{HeaderName, ACCEPT, AUTHORIZATION, CONTENT_TYPE},
+    Body, Client, Method, Request, Response, Uri,
+};
+use serde_json::Value;
 use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+pub async fn write_test_endpoint(init: &SetupProfile) {
+    write_endpoint_url(init, "http://localhost:8080").await;
+}
+
+pub async fn write_endpoint_url(init: &SetupProfile, url: &str) {
+    let endpoint = EndpointDidSov::create()
+        .set_service_endpoint(url.parse().unwrap())
+        .set_routing_keys(Some(vec!["key1".to_string(), "key2".to_string()]))
+        .set_types(Some(vec![DidSovServiceType::Endpoint]));
+    write_endpoint(&init.profile, &init.institution_did, &endpoint)
+        .await
+        .unwrap();
+    thread::sleep(Duration::from_millis(50));
+}
+
+pub async fn body_to_json(response: Response<Body>) -> Value {
+    let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    serde_json::from_slice(&body_bytes).unwrap()
+}
+
+/// Port of the driver with the default configuration, which most tests run against.
+pub const DRIVER_PORT: u16 = 4000;
 
 pub fn send_request(did: &str) -> ResponseFuture {
-    Client::new()
-        .get(Uri::from_str(&format!("http://localhost:4000/1.0/identifiers/{}", did)).unwrap())
+    send_request_to(DRIVER_PORT, did)
+}
+
+pub fn send_request_to(port: u16, did: &str) -> ResponseFuture {
+    Client::new().get(
+        Uri::from_str(&format!(
+            "http://localhost:{}/1.0/identifiers/{}",
+            port, did
+        ))
+        .unwrap(),
+    )
+}
+
+pub fn send_request_with_accept(did: &str, accept: &str) -> ResponseFuture {
+    send_request_with_header(did, ACCEPT, accept)
+}
+
+pub fn send_request_with_header(did: &str, name: HeaderName, value: &str) -> ResponseFuture {
+    let request = Request::get(format!("http://localhost:4000/1.0/identifiers/{}", did))
+        .header(name, value)
+        .body(Body::empty())
+        .unwrap();
+    Client::new().request(request)
+}
+
+pub fn send_request_with_query(did: &str, query: &str) -> ResponseFuture {
+    Client::new().get(
+        Uri::from_str(&format!(
+            "http://localhost:4000/1.0/identifiers/{}?{}",
+            did, query
+        ))
+        .unwrap(),
+    )
+}
+
+pub const ADMIN_TOKEN: &str = "admin-token";
+
+pub fn send_admin_request(method: Method, path: &str, token: Option<&str>) -> ResponseFuture {
+    send_admin_request_to(DRIVER_PORT, method, path, token)
+}
+
+pub fn send_admin_request_to(
+    port: u16,
+    method: Method,
+    path: &str,
+    token: Option<&str>,
+) -> ResponseFuture {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(format!("http://localhost:{}/admin/cache{}", port, path));
+    if let Some(token) = token {
+        request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+    }
+    Client::new().request(request.body(Body::empty()).unwrap())
+}
+
+/// Drops the cached resolution of `did` from the driver listening on `port`.
+pub async fn purge_did(port: u16, did: &str) {
+    let response = send_admin_request_to(
+        port,
+        Method::DELETE,
+        &format!("/{}", did),
+        Some(ADMIN_TOKEN),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::NO_CONTENT);
+}
+
+pub async fn cache_stats(port: u16) -> Value {
+    let response = send_admin_request_to(port, Method::GET, "/stats", Some(ADMIN_TOKEN))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    body_to_json(response).await
+}
+
+/// Sums the samples of a counter-like metric across all of its label sets.
+pub async fn metric_total(name: &str) -> f64 {
+    let response = send_get_request("/metrics").await.unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    let body_bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    String::from_utf8(body_bytes.to_vec())
+        .unwrap()
+        .lines()
+        .filter(|line| {
+            line.starts_with(&format!("{}{{", name)) || line.starts_with(&format!("{} ", name))
+        })
+        .filter_map(|line| line.rsplit(' ').next()?.parse::<f64>().ok())
+        .sum()
+}
+
+pub fn send_batch_request(dids: &[&str]) -> ResponseFuture {
+    let request = Request::post("http://localhost:4000/1.0/identifiers")
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_vec(dids).unwrap()))
+        .unwrap();
+    Client::new().request(request)
+}
+
+pub fn send_get_request(path: &str) -> ResponseFuture {
+    send_get_request_to(DRIVER_PORT, path)
+}
+
+pub fn send_get_request_to(port: u16, path: &str) -> ResponseFuture {
+    Client::new().get(Uri::from_str(&format!("http://localhost:{}{}", port, path)).unwrap())
 }